@@ -1,6 +1,12 @@
 use bitstream_io::{BigEndian, BitWrite, BitWriter};
 
-fn get_alphanumeric_value(c: char) -> Option<u8> {
+/// Largest number of characters an M4-L symbol holds in alphanumeric mode.
+pub const MAX_INPUT_LENGTH: usize = 21;
+
+/// The alphanumeric mode character set, each character at the index of its value.
+pub const CHARSET: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+pub fn get_alphanumeric_value(c: char) -> Option<u8> {
     match c {
         '0'..='9' => Some(c as u8 - b'0'),
         'A'..='Z' => Some(c as u8 - b'A' + 10),
@@ -17,6 +23,29 @@ fn get_alphanumeric_value(c: char) -> Option<u8> {
     }
 }
 
+pub fn validate_input(s: &str) -> Result<String, String> {
+    if s.len() > MAX_INPUT_LENGTH {
+        return Err(String::from("Input must not exceed 21 characters"));
+    }
+
+    if !s.chars().all(|c| get_alphanumeric_value(c).is_some()) {
+        return Err(String::from("Input contains invalid characters. Only alphanumeric (0-9, A-Z) and special characters ( $%*+-./:) are allowed"));
+    }
+
+    Ok(s.to_string())
+}
+
+/// Validates input read from a file or standard input, dropping a single trailing line ending.
+pub fn validate_bytes(bytes: &[u8]) -> Result<String, String> {
+    let s = std::str::from_utf8(bytes).map_err(|_| String::from("Input is not valid text"))?;
+    let s = s
+        .strip_suffix('\n')
+        .map(|s| s.strip_suffix('\r').unwrap_or(s))
+        .unwrap_or(s);
+
+    validate_input(s)
+}
+
 pub fn encode(input: &str) -> Result<Vec<u8>, std::io::Error> {
     let mut result = Vec::new();
     let mut writer = BitWriter::endian(&mut result, BigEndian);
@@ -29,7 +58,8 @@ pub fn encode(input: &str) -> Result<Vec<u8>, std::io::Error> {
 
     // Encode pairs of characters
     let chars: Vec<char> = input.chars().collect();
-    let mut bits_written = 0;
+    // The mode indicator and character count take the first 8 bits
+    let mut bits_written = 8;
 
     for pair in chars.chunks(2) {
         if pair.len() == 2 {
@@ -202,6 +232,9 @@ mod tests {
             terminator, 0,
             "Truncated terminator bits should be all zeros"
         );
+
+        // The terminator must not spill into a 17th codeword
+        assert_eq!(result.len(), 16);
     }
 
     #[test]
@@ -359,4 +392,13 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_validate_bytes() {
+        assert_eq!(validate_bytes(b"HELLO\n").unwrap(), "HELLO");
+        assert_eq!(validate_bytes(b"HELLO\r\n").unwrap(), "HELLO");
+        assert_eq!(validate_bytes(b"HELLO ").unwrap(), "HELLO ");
+        assert!(validate_bytes(b"HELLO\n\n").is_err());
+        assert!(validate_bytes(&[0xff]).is_err());
+    }
 }
@@ -0,0 +1,389 @@
+use crate::code_matrix::CodeMatrix;
+use crate::output_format::OutputFormat;
+use crate::symbol_image::ImageOptions;
+use crate::{symbol_matrix, symbol_output, template};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InputFormat {
+    Csv,
+    /// JSON Lines, one object per line
+    Jsonl,
+}
+
+impl InputFormat {
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let extension = Path::new(file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())?
+            .to_ascii_lowercase();
+
+        match extension.as_str() {
+            "csv" => Some(InputFormat::Csv),
+            "jsonl" | "ndjson" => Some(InputFormat::Jsonl),
+            _ => None,
+        }
+    }
+}
+
+/// One symbol to generate. Everything except the data is optional and falls back to the batch settings.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BatchRow {
+    pub data: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default, alias = "ec_level")]
+    pub ec: Option<String>,
+    #[serde(default)]
+    pub mask: Option<u8>,
+    #[serde(default)]
+    pub filename: Option<String>,
+}
+
+/// A row as read from the input, numbered from 1, or the reason it could not be read.
+pub type ParsedRow = (usize, Result<BatchRow, String>);
+
+pub struct BatchSettings {
+    pub options: ImageOptions,
+    /// File name template, see [`file_name`]
+    pub filename_template: String,
+    pub output_dir: PathBuf,
+    /// Output format for every row, otherwise taken from each file name
+    pub format: Option<OutputFormat>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RowResult {
+    pub row: usize,
+    pub data: Option<String>,
+    pub file: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report<'a> {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub rows: &'a [RowResult],
+}
+
+impl<'a> Report<'a> {
+    pub fn new(rows: &'a [RowResult]) -> Self {
+        let failed = rows.iter().filter(|row| row.error.is_some()).count();
+
+        Self {
+            total: rows.len(),
+            succeeded: rows.len() - failed,
+            failed,
+            rows,
+        }
+    }
+}
+
+pub fn read_rows(reader: impl Read, format: InputFormat) -> Vec<ParsedRow> {
+    match format {
+        InputFormat::Csv => read_csv(reader),
+        InputFormat::Jsonl => read_jsonl(reader),
+    }
+}
+
+fn read_csv(reader: impl Read) -> Vec<ParsedRow> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    csv_reader
+        .deserialize::<BatchRow>()
+        .enumerate()
+        .map(|(i, row)| (i + 1, row.map_err(|err| err.to_string())))
+        .collect()
+}
+
+fn read_jsonl(reader: impl Read) -> Vec<ParsedRow> {
+    BufReader::new(reader)
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| match line {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => Some((
+                i + 1,
+                serde_json::from_str(&line).map_err(|err| err.to_string()),
+            )),
+            Err(err) => Some((i + 1, Err(err.to_string()))),
+        })
+        .collect()
+}
+
+/// Generates every row across `jobs` worker threads, returning the results in row order.
+pub fn run(rows: &[ParsedRow], settings: &BatchSettings, jobs: usize) -> Vec<RowResult> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(rows.len()));
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, rows.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+
+                let Some((row, parsed)) = rows.get(i) else {
+                    break;
+                };
+
+                let result = match parsed {
+                    Ok(batch_row) => generate_row(*row, batch_row, settings),
+                    Err(err) => RowResult {
+                        row: *row,
+                        data: None,
+                        file: None,
+                        error: Some(err.clone()),
+                    },
+                };
+
+                results.lock().unwrap().push(result);
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|result| result.row);
+
+    results
+}
+
+fn generate_row(row: usize, batch_row: &BatchRow, settings: &BatchSettings) -> RowResult {
+    let mut result = RowResult {
+        row,
+        data: Some(batch_row.data.clone()),
+        file: None,
+        error: None,
+    };
+
+    match write_row(row, batch_row, settings) {
+        Ok(file) => result.file = Some(file),
+        Err(err) => result.error = Some(err),
+    }
+
+    result
+}
+
+fn write_row(row: usize, batch_row: &BatchRow, settings: &BatchSettings) -> Result<String, String> {
+    let matrix = symbol(batch_row)?;
+    let data = &batch_row.data;
+
+    let template = batch_row
+        .filename
+        .as_deref()
+        .unwrap_or(&settings.filename_template);
+    let name = file_name(template, row, data)?;
+    let path = settings.output_dir.join(check_relative(&name)?);
+    let path = path.to_string_lossy().to_string();
+
+    let format = settings
+        .format
+        .or_else(|| OutputFormat::from_file_name(&path))
+        .ok_or_else(|| format!("Cannot tell the output format of {}", path))?;
+
+    let options = settings.options.clone().with_hri_text(data);
+
+    symbol_output::write(&path, format, &options, &matrix).map_err(|err| err.to_string())?;
+
+    Ok(path)
+}
+
+/// Validates a row and generates its symbol.
+pub fn symbol(batch_row: &BatchRow) -> Result<CodeMatrix, String> {
+    check_symbol_type(batch_row)?;
+
+    symbol_matrix::encode(&batch_row.data, batch_row.mask)
+}
+
+/// Only M4-L symbols can be generated, so any other version or error correction level is rejected.
+fn check_symbol_type(batch_row: &BatchRow) -> Result<(), String> {
+    if let Some(version) = batch_row.version.as_deref().filter(|v| !v.is_empty()) {
+        if !version.eq_ignore_ascii_case("M4") {
+            return Err(format!(
+                "Unsupported version {}, only M4 is supported",
+                version
+            ));
+        }
+    }
+
+    if let Some(ec) = batch_row.ec.as_deref().filter(|ec| !ec.is_empty()) {
+        if !ec.eq_ignore_ascii_case("L") {
+            return Err(format!(
+                "Unsupported error correction level {}, only L is supported",
+                ec
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Expands a file name template. `{row}` is the row number, optionally zero padded as `{row:06}`,
+/// and `{data}` is the encoded data with characters that are unsafe in file names replaced by `_`.
+pub fn file_name(template: &str, row: usize, data: &str) -> Result<String, String> {
+    template::expand(
+        template,
+        &[("row", row as u64)],
+        &[("data", &safe_file_name(data))],
+    )
+}
+
+/// Rejects file names that would land outside the output directory: absolute paths,
+/// drive prefixes and `..` segments.
+fn check_relative(name: &str) -> Result<&Path, String> {
+    let path = Path::new(name);
+    let inside = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+
+    if !inside || path.file_name().is_none() {
+        return Err(format!(
+            "File name {} must be a relative path inside the output directory",
+            name
+        ));
+    }
+
+    Ok(path)
+}
+
+pub fn safe_file_name(data: &str) -> String {
+    data.chars()
+        .map(|c| match c {
+            '0'..='9' | 'A'..='Z' | '-' | '.' | '+' | '%' | '$' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_csv() {
+        let input = "data,version,ec,mask,filename\nHELLO,M4,L,2,hello.png\nWORLD,,,,\n";
+        let rows = read_rows(input.as_bytes(), InputFormat::Csv);
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0].1.as_ref().unwrap(),
+            &BatchRow {
+                data: String::from("HELLO"),
+                version: Some(String::from("M4")),
+                ec: Some(String::from("L")),
+                mask: Some(2),
+                filename: Some(String::from("hello.png")),
+            }
+        );
+        assert_eq!(rows[1].0, 2);
+        assert_eq!(rows[1].1.as_ref().unwrap().mask, None);
+    }
+
+    #[test]
+    fn test_read_csv_data_only() {
+        let rows = read_rows("data\nA1\nB2\n".as_bytes(), InputFormat::Csv);
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].1.as_ref().unwrap().data, "B2");
+    }
+
+    #[test]
+    fn test_read_jsonl() {
+        let input = "{\"data\": \"HELLO\", \"mask\": 1}\n\n{\"data\": 5}\n{\"data\": \"X\", \"ec_level\": \"L\"}\n";
+        let rows = read_rows(input.as_bytes(), InputFormat::Jsonl);
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].1.as_ref().unwrap().mask, Some(1));
+        assert_eq!(rows[1].0, 3);
+        assert!(rows[1].1.is_err());
+        assert_eq!(rows[2].1.as_ref().unwrap().ec.as_deref(), Some("L"));
+    }
+
+    #[test]
+    fn test_file_name_template() {
+        assert_eq!(file_name("{row}.png", 7, "A").unwrap(), "7.png");
+        assert_eq!(
+            file_name("label-{row:05}.svg", 42, "A").unwrap(),
+            "label-00042.svg"
+        );
+        assert_eq!(
+            file_name("{data}.png", 1, "LOT/12 A:B").unwrap(),
+            "LOT_12_A_B.png"
+        );
+        assert!(file_name("{serial}.png", 1, "A").is_err());
+        assert!(file_name("{row.png", 1, "A").is_err());
+    }
+
+    #[test]
+    fn test_check_relative() {
+        assert!(check_relative("label.png").is_ok());
+        assert!(check_relative("./labels/label.png").is_ok());
+        assert!(check_relative("../label.png").is_err());
+        assert!(check_relative("labels/../../label.png").is_err());
+        assert!(check_relative("/tmp/label.png").is_err());
+        assert!(check_relative("").is_err());
+        assert!(check_relative("labels/..").is_err());
+    }
+
+    #[test]
+    fn test_check_symbol_type() {
+        let row = |version: &str, ec: &str| BatchRow {
+            data: String::from("A"),
+            version: Some(version.to_string()),
+            ec: Some(ec.to_string()),
+            mask: None,
+            filename: None,
+        };
+
+        assert!(check_symbol_type(&row("m4", "l")).is_ok());
+        assert!(check_symbol_type(&row("", "")).is_ok());
+        assert!(check_symbol_type(&row("M3", "L")).is_err());
+        assert!(check_symbol_type(&row("M4", "Q")).is_err());
+    }
+
+    #[test]
+    fn test_run_reports_each_row() {
+        let output_dir = std::env::temp_dir().join(format!("qrc-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&output_dir).unwrap();
+
+        let settings = BatchSettings {
+            options: ImageOptions::new(1),
+            filename_template: String::from("{row:03}.pbm"),
+            output_dir: output_dir.clone(),
+            format: None,
+        };
+
+        let rows = read_rows(
+            "data,mask,filename\nHELLO,,\nhello,,\nWORLD,3,\nAGAIN,7,\nESCAPE,,../escape.pbm\n"
+                .as_bytes(),
+            InputFormat::Csv,
+        );
+        let results = run(&rows, &settings, 3);
+
+        assert_eq!(
+            results.iter().map(|r| r.row).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5]
+        );
+        assert!(results[0].error.is_none());
+        assert!(results[1].error.is_some());
+        assert!(results[2].error.is_none());
+        assert!(results[3].error.is_some());
+        assert!(results[4].error.is_some());
+        assert!(!output_dir.join("../escape.pbm").exists());
+        assert!(output_dir.join("001.pbm").exists());
+        assert!(output_dir.join("003.pbm").exists());
+
+        let report = Report::new(&results);
+        assert_eq!((report.total, report.succeeded, report.failed), (5, 2, 3));
+
+        std::fs::remove_dir_all(output_dir).unwrap();
+    }
+}
@@ -0,0 +1,244 @@
+//! Dark and light pixels from reflectance, by one threshold for the whole image or by local
+//! thresholds that follow shadows and glare across it.
+
+use clap::ValueEnum;
+use image::{GrayImage, Luma};
+
+// Lowest reflectance difference across the image that could be a symbol
+const MIN_CONTRAST: f64 = 0.2;
+
+// Neighbourhoods with a lower reflectance standard deviation hold no edge, and are taken as a
+// whole to be dark or light by the global threshold
+const MIN_LOCAL_DEVIATION: f64 = 0.05;
+
+// Dynamic range of the standard deviation in Sauvola's formula, for reflectance from 0 to 1
+const SAUVOLA_RANGE: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Method {
+    /// Halfway between the lowest and highest reflectance in the image
+    Global,
+    /// Local mean, lowered where the neighbourhood has little contrast (Sauvola)
+    Sauvola,
+    /// Local mean plus a multiple of the local standard deviation (Niblack)
+    Niblack,
+    /// Local mean less a fixed offset
+    BlockMean,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Binariser {
+    pub method: Method,
+    /// Side of the square neighbourhood of each pixel, in pixels
+    pub window: u32,
+    /// Sauvola's k, Niblack's k or the block mean offset
+    pub k: f64,
+}
+
+impl Binariser {
+    pub const GLOBAL: Self = Self {
+        method: Method::Global,
+        window: 1,
+        k: 0.0,
+    };
+
+    /// The method with its usual k and a window a quarter of the smaller image side, wide enough to
+    /// take in the edges around the 3x3 module centre of a finder pattern filling the image.
+    pub fn new(method: Method, width: u32, height: u32) -> Self {
+        Self {
+            method,
+            window: (width.min(height) / 4).max(15) | 1,
+            k: method.default_k(),
+        }
+    }
+
+    /// Threshold reflectance of every pixel, row by row. Darker pixels are dark.
+    pub fn thresholds(
+        &self,
+        reflectance: &[f64],
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<f64>, String> {
+        let min = reflectance.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = reflectance
+            .iter()
+            .cloned()
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        if max - min < MIN_CONTRAST {
+            return Err(String::from("No symbol found: the image has no contrast"));
+        }
+
+        let global = (min + max) / 2.0;
+
+        if self.method == Method::Global {
+            return Ok(vec![global; reflectance.len()]);
+        }
+
+        let sums = Integral::new(reflectance, width, height, |r| r);
+        let squares = Integral::new(reflectance, width, height, |r| r * r);
+        let half = self.window / 2;
+        let mut thresholds = Vec::with_capacity(reflectance.len());
+
+        for y in 0..height {
+            for x in 0..width {
+                let (left, top) = (x.saturating_sub(half), y.saturating_sub(half));
+                let (right, bottom) = ((x + half + 1).min(width), (y + half + 1).min(height));
+                let count = ((right - left) * (bottom - top)) as f64;
+
+                let mean = sums.sum(left, top, right, bottom) / count;
+                let variance = squares.sum(left, top, right, bottom) / count - mean * mean;
+                let deviation = variance.max(0.0).sqrt();
+
+                thresholds.push(match self.method {
+                    _ if deviation < MIN_LOCAL_DEVIATION => global,
+                    Method::Sauvola => mean * (1.0 + self.k * (deviation / SAUVOLA_RANGE - 1.0)),
+                    Method::Niblack => mean + self.k * deviation,
+                    Method::BlockMean => mean - self.k,
+                    Method::Global => global,
+                });
+            }
+        }
+
+        Ok(thresholds)
+    }
+
+    /// Pixels darker than their threshold, row by row.
+    pub fn dark_pixels(
+        &self,
+        reflectance: &[f64],
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<bool>, String> {
+        Ok(reflectance
+            .iter()
+            .zip(self.thresholds(reflectance, width, height)?)
+            .map(|(&r, threshold)| r < threshold)
+            .collect())
+    }
+}
+
+impl Method {
+    pub fn default_k(self) -> f64 {
+        match self {
+            Method::Global => 0.0,
+            Method::Sauvola => 0.2,
+            Method::Niblack => -0.2,
+            Method::BlockMean => 0.05,
+        }
+    }
+}
+
+/// Summed area table, for the sum over any rectangle in four lookups.
+struct Integral {
+    width: u32,
+    sums: Vec<f64>,
+}
+
+impl Integral {
+    fn new(values: &[f64], width: u32, height: u32, f: impl Fn(f64) -> f64) -> Self {
+        let stride = width as usize + 1;
+        let mut sums = vec![0.0; stride * (height as usize + 1)];
+
+        for y in 0..height as usize {
+            let mut row = 0.0;
+
+            for x in 0..width as usize {
+                row += f(values[y * width as usize + x]);
+                sums[(y + 1) * stride + x + 1] = sums[y * stride + x + 1] + row;
+            }
+        }
+
+        Self { width, sums }
+    }
+
+    /// Sum over the columns from `left` up to `right` and rows from `top` up to `bottom`.
+    fn sum(&self, left: u32, top: u32, right: u32, bottom: u32) -> f64 {
+        let stride = self.width as usize + 1;
+        let at = |x: u32, y: u32| self.sums[y as usize * stride + x as usize];
+
+        at(right, bottom) - at(left, bottom) - at(right, top) + at(left, top)
+    }
+}
+
+/// Black and white image of the dark pixels, for checking what the decoder sees.
+pub fn image(dark: &[bool], width: u32, height: u32) -> GrayImage {
+    GrayImage::from_fn(width, height, |x, y| match dark[(y * width + x) as usize] {
+        true => Luma([0]),
+        false => Luma([255]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Alternating dark and light 4 pixel stripes under light falling off from left to right.
+    fn shaded_stripes() -> Vec<f64> {
+        (0..40)
+            .flat_map(|_| (0..80).map(|x| (1.0 - x as f64 / 100.0) * [0.9, 0.1][(x / 4) % 2]))
+            .collect()
+    }
+
+    #[test]
+    fn test_global_threshold() {
+        let reflectance = shaded_stripes();
+        let thresholds = Binariser::new(Method::Global, 80, 40)
+            .thresholds(&reflectance, 80, 40)
+            .unwrap();
+
+        // The light stripes on the shaded side fall below the global threshold
+        assert!(reflectance[79 - 4] < thresholds[79 - 4]);
+    }
+
+    #[test]
+    fn test_local_thresholds_follow_shading() {
+        let reflectance = shaded_stripes();
+
+        for method in [Method::Sauvola, Method::Niblack, Method::BlockMean] {
+            let thresholds = Binariser::new(method, 80, 40)
+                .thresholds(&reflectance, 80, 40)
+                .unwrap();
+
+            for x in 0..80 {
+                let i = 20 * 80 + x;
+                assert_eq!(
+                    reflectance[i] < thresholds[i],
+                    (x / 4) % 2 == 1,
+                    "{:?} at {}",
+                    method,
+                    x
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_flat_areas_use_global_threshold() {
+        let mut reflectance = vec![0.9; 60 * 60];
+        reflectance[..60 * 10].fill(0.05);
+
+        let thresholds = Binariser::new(Method::Sauvola, 60, 60)
+            .thresholds(&reflectance, 60, 60)
+            .unwrap();
+
+        assert!(reflectance[0] < thresholds[0]);
+        assert!(reflectance[59 * 60] > thresholds[59 * 60]);
+    }
+
+    #[test]
+    fn test_integral_sum() {
+        let values: Vec<f64> = (0..12).map(f64::from).collect();
+        let integral = Integral::new(&values, 4, 3, |v| v);
+
+        assert_eq!(integral.sum(0, 0, 4, 3), 66.0);
+        assert_eq!(integral.sum(1, 1, 3, 3), 5.0 + 6.0 + 9.0 + 10.0);
+    }
+
+    #[test]
+    fn test_no_contrast() {
+        let binariser = Binariser::new(Method::Sauvola, 10, 10);
+
+        assert!(binariser.thresholds(&[0.5; 100], 10, 10).is_err());
+    }
+}
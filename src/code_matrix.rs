@@ -2,10 +2,112 @@ use crate::bit_block::{BitBlock, DownwardsBlock, UpwardsBlock};
 use crate::data_mask::DataMask;
 use crate::pattern_scoring::PatternScoring;
 
+#[derive(Clone, Copy)]
+enum Direction {
+    Upwards,
+    Downwards,
+}
+
+// Top-left module of the 2x4 block holding each codeword, in placement order
+const CODEWORD_BLOCKS: [(usize, usize, Direction); 24] = [
+    (13, 15, Direction::Upwards),
+    (9, 15, Direction::Upwards),
+    (5, 15, Direction::Upwards),
+    (1, 15, Direction::Upwards),
+    (1, 13, Direction::Downwards),
+    (5, 13, Direction::Downwards),
+    (9, 13, Direction::Downwards),
+    (13, 13, Direction::Downwards),
+    (13, 11, Direction::Upwards),
+    (9, 11, Direction::Upwards),
+    (5, 11, Direction::Upwards),
+    (1, 11, Direction::Upwards),
+    (1, 9, Direction::Downwards),
+    (5, 9, Direction::Downwards),
+    (9, 9, Direction::Downwards),
+    (13, 9, Direction::Downwards),
+    (13, 7, Direction::Upwards),
+    (9, 7, Direction::Upwards),
+    (9, 5, Direction::Downwards),
+    (13, 5, Direction::Downwards),
+    (13, 3, Direction::Upwards),
+    (9, 3, Direction::Upwards),
+    (9, 1, Direction::Downwards),
+    (13, 1, Direction::Downwards),
+];
+
+/// What a module of the symbol is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleRole {
+    Finder,
+    Separator,
+    Timing,
+    /// Bit of the format information word, 0 being the least significant
+    FormatInfo(u8),
+    /// Bit of a codeword in placement order, 0 being the least significant. The first 16 are
+    /// data codewords and the last 8 error correction codewords
+    Codeword {
+        index: usize,
+        bit: u8,
+    },
+}
+
+/// The role of the module at row `y`, column `x`, following the layout used to build the matrix.
+pub fn module_role(y: usize, x: usize) -> ModuleRole {
+    if y < 7 && x < 7 {
+        return ModuleRole::Finder;
+    }
+
+    if y < 8 && x < 8 && (y == 7 || x == 7) {
+        return ModuleRole::Separator;
+    }
+
+    if y == 0 || x == 0 {
+        return ModuleRole::Timing;
+    }
+
+    if x == 8 && y <= 8 {
+        return ModuleRole::FormatInfo(y as u8 - 1);
+    }
+
+    if y == 8 && x < 8 {
+        return ModuleRole::FormatInfo(15 - x as u8);
+    }
+
+    CODEWORD_BLOCKS
+        .iter()
+        .enumerate()
+        .find_map(|(index, &(block_y, block_x, direction))| {
+            let (i, j) = (y.checked_sub(block_y)?, x.checked_sub(block_x)?);
+
+            if i >= 4 || j >= 2 {
+                return None;
+            }
+
+            let bit = match direction {
+                Direction::Upwards => 2 * i + j,
+                Direction::Downwards => 2 * (3 - i) + j,
+            };
+
+            Some(ModuleRole::Codeword {
+                index,
+                bit: bit as u8,
+            })
+        })
+        .unwrap()
+}
+
+#[derive(Clone)]
 pub struct CodeMatrix {
     data: [[u8; 17]; 17],
 }
 
+impl Default for CodeMatrix {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CodeMatrix {
     pub fn new() -> Self {
         Self {
@@ -49,18 +151,6 @@ impl CodeMatrix {
         matrix
     }
 
-    fn clone(&self) -> Self {
-        let mut instance = Self::new();
-
-        for i in 0..17 {
-            for j in 0..17 {
-                instance.copy(i, j, self);
-            }
-        }
-
-        instance
-    }
-
     pub fn with_data(data: &[u8]) -> Self {
         let mut matrix = Self::new();
 
@@ -71,37 +161,69 @@ impl CodeMatrix {
     }
 
     fn write_data(&mut self, data: &[u8]) {
-        self.write_block(&UpwardsBlock::new(data[0]), 13, 15);
-        self.write_block(&UpwardsBlock::new(data[1]), 9, 15);
-        self.write_block(&UpwardsBlock::new(data[2]), 5, 15);
-        self.write_block(&UpwardsBlock::new(data[3]), 1, 15);
+        for (&codeword, &(y, x, direction)) in data.iter().zip(CODEWORD_BLOCKS.iter()) {
+            match direction {
+                Direction::Upwards => self.write_block(&UpwardsBlock::new(codeword), y, x),
+                Direction::Downwards => self.write_block(&DownwardsBlock::new(codeword), y, x),
+            }
+        }
+    }
 
-        self.write_block(&DownwardsBlock::new(data[4]), 1, 13);
-        self.write_block(&DownwardsBlock::new(data[5]), 5, 13);
-        self.write_block(&DownwardsBlock::new(data[6]), 9, 13);
-        self.write_block(&DownwardsBlock::new(data[7]), 13, 13);
+    /// Reads the codewords back out of an unmasked matrix, in the order they were written.
+    pub fn read_data(&self) -> Vec<u8> {
+        CODEWORD_BLOCKS
+            .iter()
+            .map(|&(y, x, direction)| {
+                let mut codeword = 0;
+
+                for i in 0..4 {
+                    for j in 0..2 {
+                        let bit = match direction {
+                            Direction::Upwards => 2 * i + j,
+                            Direction::Downwards => 2 * (3 - i) + j,
+                        };
+
+                        codeword |= self.read(y + i, x + j) << bit;
+                    }
+                }
 
-        self.write_block(&UpwardsBlock::new(data[8]), 13, 11);
-        self.write_block(&UpwardsBlock::new(data[9]), 9, 11);
-        self.write_block(&UpwardsBlock::new(data[10]), 5, 11);
-        self.write_block(&UpwardsBlock::new(data[11]), 1, 11);
+                codeword
+            })
+            .collect()
+    }
 
-        self.write_block(&DownwardsBlock::new(data[12]), 1, 9);
-        self.write_block(&DownwardsBlock::new(data[13]), 5, 9);
-        self.write_block(&DownwardsBlock::new(data[14]), 9, 9);
-        self.write_block(&DownwardsBlock::new(data[15]), 13, 9);
+    /// A symbol `size` modules a side holding `codewords`, each `lengths` bits long. A 4 bit codeword
+    /// is taken from the high nibble.
+    pub fn with_codewords(size: usize, lengths: &[u32], codewords: &[u8]) -> Self {
+        let mut matrix = Self::new();
+        let mut modules = data_modules(size).into_iter();
 
-        self.write_block(&UpwardsBlock::new(data[16]), 13, 7);
-        self.write_block(&UpwardsBlock::new(data[17]), 9, 7);
+        matrix.init();
 
-        self.write_block(&DownwardsBlock::new(data[18]), 9, 5);
-        self.write_block(&DownwardsBlock::new(data[19]), 13, 5);
+        for (&codeword, &length) in codewords.iter().zip(lengths) {
+            for bit in 0..length {
+                let (y, x) = modules.next().unwrap();
+                matrix.write(y, x, (codeword >> (7 - bit)) & 1);
+            }
+        }
 
-        self.write_block(&UpwardsBlock::new(data[20]), 13, 3);
-        self.write_block(&UpwardsBlock::new(data[21]), 9, 3);
+        matrix
+    }
 
-        self.write_block(&DownwardsBlock::new(data[22]), 9, 1);
-        self.write_block(&DownwardsBlock::new(data[23]), 13, 1);
+    /// Reads the codewords of a symbol `size` modules a side out of an unmasked matrix, each `lengths`
+    /// bits long. A 4 bit codeword is returned in the high nibble, as error correction treats it.
+    pub fn read_codewords(&self, size: usize, lengths: &[u32]) -> Vec<u8> {
+        let mut modules = data_modules(size).into_iter();
+
+        lengths
+            .iter()
+            .map(|&length| {
+                (0..length).fold(0, |acc, bit| {
+                    let (y, x) = modules.next().unwrap();
+                    acc | self.read(y, x) << (7 - bit)
+                })
+            })
+            .collect()
     }
 
     fn write_block(&mut self, block: &impl BitBlock, y: usize, x: usize) {
@@ -112,10 +234,6 @@ impl CodeMatrix {
         }
     }
 
-    fn copy(&mut self, y: usize, x: usize, other: &Self) {
-        self.write(y, x, other.read(y, x));
-    }
-
     pub fn read(&self, y: usize, x: usize) -> u8 {
         self.data[y][x]
     }
@@ -146,6 +264,52 @@ impl CodeMatrix {
 
         matrix
     }
+
+    /// The matrix mirrored about its main diagonal, as a symbol marked on the back of a clear part is
+    /// seen from the front. The finder pattern stays in the top left corner.
+    pub fn transposed(&self) -> Self {
+        let mut matrix = Self::new();
+
+        for i in 0..17 {
+            for j in 0..17 {
+                matrix.write(j, i, self.read(i, j));
+            }
+        }
+
+        matrix
+    }
+
+    /// The 15 format information bits, in the positions written by `with_format_info`.
+    pub fn read_format_info(&self) -> u16 {
+        let positions = (1..=8).map(|y| (y, 8)).chain((1..=7).rev().map(|x| (8, x)));
+
+        positions.enumerate().fold(0, |acc, (bit, (y, x))| {
+            acc | (self.read(y, x) as u16) << bit
+        })
+    }
+}
+
+/// The data modules of a symbol `size` modules a side in placement order: up and down two module
+/// wide columns from the bottom right corner, right module first, skipping the function patterns.
+pub fn data_modules(size: usize) -> Vec<(usize, usize)> {
+    let mut modules = Vec::new();
+
+    for (pair, right) in (2..size).rev().step_by(2).enumerate() {
+        let rows: Vec<usize> = match pair % 2 {
+            0 => (1..size).rev().collect(),
+            _ => (1..size).collect(),
+        };
+
+        for y in rows {
+            for x in [right, right - 1] {
+                if y > 8 || x > 8 {
+                    modules.push((y, x));
+                }
+            }
+        }
+    }
+
+    modules
 }
 
 impl PatternScoring for CodeMatrix {
@@ -154,7 +318,7 @@ impl PatternScoring for CodeMatrix {
     }
 
     fn count_bottom_edge(&self) -> u8 {
-        self.data[16].iter().fold(0, |acc, &v| acc + v)
+        self.data[16].iter().sum()
     }
 }
 
@@ -163,6 +327,43 @@ mod tests {
     use super::*;
     use crate::bit_block::UpwardsBlock;
 
+    #[test]
+    fn test_module_roles() {
+        assert_eq!(module_role(3, 3), ModuleRole::Finder);
+        assert_eq!(module_role(7, 2), ModuleRole::Separator);
+        assert_eq!(module_role(2, 7), ModuleRole::Separator);
+        assert_eq!(module_role(0, 7), ModuleRole::Separator);
+        assert_eq!(module_role(0, 12), ModuleRole::Timing);
+        assert_eq!(module_role(16, 0), ModuleRole::Timing);
+        assert_eq!(module_role(1, 8), ModuleRole::FormatInfo(0));
+        assert_eq!(module_role(8, 8), ModuleRole::FormatInfo(7));
+        assert_eq!(module_role(8, 1), ModuleRole::FormatInfo(14));
+        assert_eq!(
+            module_role(16, 16),
+            ModuleRole::Codeword { index: 0, bit: 7 }
+        );
+        assert_eq!(
+            module_role(16, 1),
+            ModuleRole::Codeword { index: 23, bit: 0 }
+        );
+
+        // Every codeword bit has exactly one module, in the position the data is written to
+        let data: Vec<u8> = (0..24).map(|i| i * 11).collect();
+        let matrix = CodeMatrix::with_data(&data);
+        let mut modules = 0;
+
+        for y in 0..17 {
+            for x in 0..17 {
+                if let ModuleRole::Codeword { index, bit } = module_role(y, x) {
+                    assert_eq!(matrix.read(y, x), (data[index] >> bit) & 1);
+                    modules += 1;
+                }
+            }
+        }
+
+        assert_eq!(modules, 24 * 8);
+    }
+
     #[test]
     fn test_write_format_info_m4l() {
         let blank_matrix = CodeMatrix::new();
@@ -238,4 +439,58 @@ mod tests {
         assert_eq!(matrix.read(13, 16), 1);
         assert_eq!(matrix.read(13, 15), 0);
     }
+
+    #[test]
+    fn test_read_data_round_trips() {
+        let data: Vec<u8> = (0..24).map(|i| (i * 37 + 11) as u8).collect();
+        let matrix = CodeMatrix::with_data(&data);
+
+        assert_eq!(matrix.read_data(), data);
+    }
+
+    #[test]
+    fn test_read_codewords_follows_the_m4_placement() {
+        let data: Vec<u8> = (0..24).map(|i| (i * 37 + 11) as u8).collect();
+        let matrix = CodeMatrix::with_data(&data);
+
+        assert_eq!(matrix.read_codewords(17, &[8; 24]), data);
+        assert_eq!(data_modules(17).len(), 24 * 8);
+    }
+
+    #[test]
+    fn test_data_modules_fill_every_size() {
+        // M1 to M4 hold 36, 80, 132 and 192 data modules, with no remainder bits
+        for (size, modules) in [(11, 36), (13, 80), (15, 132), (17, 192)] {
+            assert_eq!(data_modules(size).len(), modules);
+        }
+
+        // M1 and M3 end their data on a 4 bit codeword
+        let lengths = [8, 8, 4, 8, 8];
+        let matrix = CodeMatrix::with_codewords(11, &lengths, &[0x12, 0x34, 0x50, 0x67, 0x89]);
+
+        assert_eq!(
+            matrix.read_codewords(11, &lengths),
+            [0x12, 0x34, 0x50, 0x67, 0x89]
+        );
+    }
+
+    #[test]
+    fn test_read_format_info_round_trips() {
+        let matrix = CodeMatrix::new().with_format_info(0b101000010011001);
+
+        assert_eq!(matrix.read_format_info(), 0b101000010011001);
+    }
+
+    #[test]
+    fn test_transposed() {
+        let mut matrix = CodeMatrix::with_data(&[0xA5; 24]);
+        matrix.write(3, 12, 1);
+        matrix.write(12, 3, 0);
+
+        let transposed = matrix.transposed();
+
+        assert_eq!(transposed.read(12, 3), 1);
+        assert_eq!(transposed.read(3, 12), 0);
+        assert_eq!(transposed.transposed().data, matrix.data);
+    }
 }
@@ -0,0 +1,91 @@
+use image::Rgba;
+
+/// Lowest symbol contrast (difference between light and dark reflectance) that typical scanners read reliably.
+pub const MIN_SYMBOL_CONTRAST: f64 = 0.4;
+
+pub const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+pub const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+pub const TRANSPARENT: Rgba<u8> = Rgba([255, 255, 255, 0]);
+
+/// Parses a colour given as `#RRGGBB`, `#RRGGBBAA` or one of `black`, `white`, `transparent`.
+pub fn parse_colour(s: &str) -> Result<Rgba<u8>, String> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "black" => return Ok(BLACK),
+        "white" => return Ok(WHITE),
+        "transparent" => return Ok(TRANSPARENT),
+        _ => {}
+    }
+
+    let hex = s.trim().trim_start_matches('#');
+
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!(
+            "Invalid colour '{}'. Use #RRGGBB, #RRGGBBAA, black, white or transparent",
+            s
+        ));
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+    let alpha = if hex.len() == 8 { channel(3) } else { 255 };
+
+    Ok(Rgba([channel(0), channel(1), channel(2), alpha]))
+}
+
+/// Reflectance of a colour in the range 0..1, taken as its relative luminance once composited over a white substrate.
+pub fn reflectance(colour: Rgba<u8>) -> f64 {
+    let Rgba([r, g, b, a]) = colour;
+    let alpha = a as f64 / 255.0;
+
+    let linear = |c: u8| {
+        let c = (c as f64 / 255.0) * alpha + (1.0 - alpha);
+
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+
+    0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+}
+
+/// Symbol contrast between two colours, as the difference of their reflectances.
+pub fn symbol_contrast(a: Rgba<u8>, b: Rgba<u8>) -> f64 {
+    (reflectance(a) - reflectance(b)).abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_colour_hex() {
+        assert_eq!(parse_colour("#000000").unwrap(), BLACK);
+        assert_eq!(parse_colour("FF8000").unwrap(), Rgba([255, 128, 0, 255]));
+        assert_eq!(parse_colour("#11223344").unwrap(), Rgba([17, 34, 51, 68]));
+    }
+
+    #[test]
+    fn test_parse_colour_names() {
+        assert_eq!(parse_colour("White").unwrap(), WHITE);
+        assert_eq!(parse_colour("transparent").unwrap()[3], 0);
+    }
+
+    #[test]
+    fn test_parse_colour_invalid() {
+        assert!(parse_colour("#12345").is_err());
+        assert!(parse_colour("#GGGGGG").is_err());
+        assert!(parse_colour("red").is_err());
+    }
+
+    #[test]
+    fn test_symbol_contrast() {
+        assert!((symbol_contrast(BLACK, WHITE) - 1.0).abs() < 1e-9);
+        assert!(symbol_contrast(BLACK, TRANSPARENT) > MIN_SYMBOL_CONTRAST);
+
+        // Mid grey on light grey is not scannable
+        let grey = parse_colour("#808080").unwrap();
+        let light_grey = parse_colour("#A0A0A0").unwrap();
+        assert!(symbol_contrast(grey, light_grey) < MIN_SYMBOL_CONTRAST);
+    }
+}
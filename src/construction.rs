@@ -0,0 +1,245 @@
+//! Step by step frames of symbol construction, written as an animated GIF for training material.
+
+use crate::code_matrix::{self, CodeMatrix, ModuleRole};
+use crate::data_mask::DataMask;
+use crate::pattern_scoring::PatternScoring;
+use crate::{colour, format_info, hri_font, symbol_debug};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, ImageError, Rgba, RgbaImage};
+
+const MODULE_COUNT: usize = 17;
+
+// Modules not placed yet
+const UNPLACED: Rgba<u8> = Rgba([190, 190, 190, 255]);
+
+// The last frame stays up this many times longer than the others
+const FINAL_HOLD: u32 = 4;
+
+pub struct Step {
+    pub caption: String,
+    pub matrix: CodeMatrix,
+    /// Number of codewords placed so far
+    codewords: usize,
+    format_info: bool,
+    /// Codeword placed in this step, drawn in its role colour
+    highlight: Option<usize>,
+}
+
+impl Step {
+    fn is_placed(&self, role: ModuleRole) -> bool {
+        match role {
+            ModuleRole::Codeword { index, .. } => index < self.codewords,
+            ModuleRole::FormatInfo(_) => self.format_info,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AnimationOptions {
+    pub module_size: u32,
+    pub quiet_zone: u32,
+    pub frame_duration_ms: u32,
+}
+
+/// Every stage of building the symbol for all 24 codewords: the function patterns, each codeword
+/// as it is placed, each candidate mask with its score, and the final symbol with format information.
+pub fn steps(codewords: &[u8], pattern_ref: Option<u8>) -> Vec<Step> {
+    let mut steps = vec![Step {
+        caption: String::from("FUNCTION PATTERNS"),
+        matrix: CodeMatrix::with_data(&[]),
+        codewords: 0,
+        format_info: false,
+        highlight: None,
+    }];
+
+    for (index, codeword) in codewords.iter().enumerate() {
+        steps.push(Step {
+            caption: format!("{}: {:02X}", symbol_debug::codeword_name(index), codeword),
+            matrix: CodeMatrix::with_data(&codewords[..=index]),
+            codewords: index + 1,
+            format_info: false,
+            highlight: Some(index),
+        });
+    }
+
+    let data_matrix = CodeMatrix::with_data(codewords);
+    let best = DataMask::best_pattern(&data_matrix).pattern_ref();
+    let selected = pattern_ref.unwrap_or(best);
+
+    for candidate in 0..4 {
+        let matrix = data_matrix.with_data_mask(&DataMask::from_pattern_ref(candidate).unwrap());
+
+        steps.push(Step {
+            caption: format!("MASK {}: SCORE {}", candidate, matrix.pattern_score()),
+            matrix,
+            codewords: codewords.len(),
+            format_info: false,
+            highlight: None,
+        });
+    }
+
+    let format_word = format_info::encode(selected);
+    let matrix = data_matrix
+        .with_data_mask(&DataMask::from_pattern_ref(selected).unwrap())
+        .with_format_info(format_word);
+
+    steps.push(Step {
+        caption: format!("MASK {} FORMAT {:04X}", selected, format_word),
+        matrix,
+        codewords: codewords.len(),
+        format_info: true,
+        highlight: None,
+    });
+
+    steps
+}
+
+/// Renders one step with its caption underneath, on a canvas of the given width.
+pub fn render_frame(options: &AnimationOptions, step: &Step, width: u32) -> RgbaImage {
+    let size = options.module_size;
+    let symbol_side = (MODULE_COUNT as u32 + 2 * options.quiet_zone) * size;
+    let font_size = size.max(hri_font::GLYPH_HEIGHT);
+    let caption_height = hri_font::text_height(font_size) + size;
+    let mut img = RgbaImage::from_pixel(width, symbol_side + caption_height, colour::WHITE);
+
+    let left = (width - symbol_side) / 2 + options.quiet_zone * size;
+    let top = options.quiet_zone * size;
+
+    for y in 0..MODULE_COUNT {
+        for x in 0..MODULE_COUNT {
+            let role = code_matrix::module_role(y, x);
+            let dark = step.matrix.read(y, x) == 1;
+
+            let fill = match role {
+                _ if !step.is_placed(role) => UNPLACED,
+                ModuleRole::Codeword { index, .. } if step.highlight == Some(index) => {
+                    symbol_debug::role_colour(role, dark)
+                }
+                _ if dark => colour::BLACK,
+                _ => colour::WHITE,
+            };
+
+            for py in 0..size {
+                for px in 0..size {
+                    img.put_pixel(
+                        left + x as u32 * size + px,
+                        top + y as u32 * size + py,
+                        fill,
+                    );
+                }
+            }
+        }
+    }
+
+    let text_left = (width - hri_font::text_width(&step.caption, font_size)) / 2;
+
+    hri_font::draw(&step.caption, font_size, |px, py| {
+        img.put_pixel(text_left + px, symbol_side + py, colour::BLACK);
+    });
+
+    img
+}
+
+/// Encodes the steps as a looping GIF, holding the final symbol longer than the other frames.
+pub fn gif(options: &AnimationOptions, steps: &[Step]) -> Result<Vec<u8>, ImageError> {
+    let size = options.module_size;
+    let symbol_side = (MODULE_COUNT as u32 + 2 * options.quiet_zone) * size;
+    let font_size = size.max(hri_font::GLYPH_HEIGHT);
+
+    // Every frame has the size of the widest caption
+    let width = steps
+        .iter()
+        .map(|step| hri_font::text_width(&step.caption, font_size) + 2 * size)
+        .fold(symbol_side, u32::max);
+
+    let mut bytes = Vec::new();
+
+    {
+        let mut encoder = GifEncoder::new(&mut bytes);
+        encoder.set_repeat(Repeat::Infinite)?;
+
+        for (i, step) in steps.iter().enumerate() {
+            let duration = match i + 1 == steps.len() {
+                true => options.frame_duration_ms * FINAL_HOLD,
+                false => options.frame_duration_ms,
+            };
+
+            encoder.encode_frame(Frame::from_parts(
+                render_frame(options, step, width),
+                0,
+                0,
+                Delay::from_numer_denom_ms(duration, 1),
+            ))?;
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data_codewords, matrix_text, symbol_matrix};
+    use image::codecs::gif::GifDecoder;
+    use image::AnimationDecoder;
+
+    const OPTIONS: AnimationOptions = AnimationOptions {
+        module_size: 4,
+        quiet_zone: 2,
+        frame_duration_ms: 250,
+    };
+
+    #[test]
+    fn test_steps() {
+        let codewords = data_codewords::generate("STEPS");
+        let steps = steps(&codewords, None);
+
+        // Function patterns, 24 codewords, 4 masks and the final symbol
+        assert_eq!(steps.len(), 1 + 24 + 4 + 1);
+        assert_eq!(steps[1].caption, format!("D0: {:02X}", codewords[0]));
+        assert_eq!(steps[24].caption, format!("E7: {:02X}", codewords[23]));
+
+        // The last step is the symbol the encoder generates
+        assert_eq!(
+            matrix_text::render(&steps[29].matrix),
+            matrix_text::render(&symbol_matrix::generate(&codewords, None))
+        );
+    }
+
+    #[test]
+    fn test_unplaced_modules() {
+        let codewords = data_codewords::generate("STEPS");
+        let steps = steps(&codewords, Some(2));
+        let frame = render_frame(&OPTIONS, &steps[1], 100);
+
+        // Codeword 0 is highlighted, codeword 1 and the format information are not placed yet
+        let pixel = |y: u32, x: u32| *frame.get_pixel(8 + 9 + x * 4, 8 + y * 4);
+        let highlighted = symbol_debug::role_colour(
+            ModuleRole::Codeword { index: 0, bit: 7 },
+            steps[1].matrix.read(16, 16) == 1,
+        );
+
+        assert_eq!(pixel(16, 16), highlighted);
+        assert_eq!(pixel(12, 16), UNPLACED);
+        assert_eq!(pixel(1, 8), UNPLACED);
+        assert_eq!(pixel(0, 0), colour::BLACK);
+        assert!(steps[29].caption.starts_with("MASK 2 FORMAT 1D5B"));
+    }
+
+    #[test]
+    fn test_gif_frames() {
+        let steps = steps(&data_codewords::generate("GIF"), None);
+        let bytes = gif(&OPTIONS, &steps).unwrap();
+
+        let frames = GifDecoder::new(std::io::Cursor::new(bytes))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+
+        assert_eq!(frames.len(), steps.len());
+        assert_eq!(frames[0].delay().numer_denom_ms(), (250, 1));
+        assert_eq!(frames[29].delay().numer_denom_ms(), (1000, 1));
+    }
+}
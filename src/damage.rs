@@ -0,0 +1,315 @@
+//! Synthetic damage to a symbol matrix, and trials of how often the decoder survives it.
+
+use crate::code_matrix::CodeMatrix;
+use crate::decoder;
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+
+const MODULE_COUNT: usize = 17;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DamageKind {
+    /// Flips this many randomly chosen modules
+    Flips,
+    /// Clears a straight scratch this many modules long at a random position and angle
+    Scratch,
+    /// Darkens a round blot of this radius in modules
+    Blot,
+    /// Tears away a triangle from a random corner, its sides this many modules long
+    Tear,
+}
+
+impl DamageKind {
+    pub fn default_severities(self) -> &'static [u32] {
+        match self {
+            DamageKind::Flips => &[0, 2, 4, 6, 8, 10, 12, 16],
+            DamageKind::Scratch => &[0, 3, 6, 9, 12, 15, 20],
+            DamageKind::Blot => &[0, 1, 2, 3, 4, 5],
+            DamageKind::Tear => &[0, 2, 4, 6, 8, 10],
+        }
+    }
+}
+
+/// Returns a copy of the matrix with one instance of the damage applied.
+pub fn apply(
+    matrix: &CodeMatrix,
+    kind: DamageKind,
+    severity: u32,
+    rng: &mut impl Rng,
+) -> CodeMatrix {
+    let mut damaged = matrix.clone();
+    let n = MODULE_COUNT as f64;
+
+    match kind {
+        DamageKind::Flips => {
+            let mut modules: Vec<(usize, usize)> = (0..MODULE_COUNT)
+                .flat_map(|y| (0..MODULE_COUNT).map(move |x| (y, x)))
+                .collect();
+
+            for _ in 0..(severity as usize).min(modules.len()) {
+                let (y, x) = modules.swap_remove(rng.gen_range(0..modules.len()));
+                damaged.write(y, x, 1 - damaged.read(y, x));
+            }
+        }
+        DamageKind::Scratch => {
+            let (mut x, mut y) = (rng.gen_range(0.0..n), rng.gen_range(0.0..n));
+            let angle: f64 = rng.gen_range(0.0..std::f64::consts::PI * 2.0);
+            let step = 0.25;
+
+            for _ in 0..(severity as f64 / step) as u32 {
+                if (0.0..n).contains(&x) && (0.0..n).contains(&y) {
+                    damaged.write(y as usize, x as usize, 0);
+                }
+
+                x += angle.cos() * step;
+                y += angle.sin() * step;
+            }
+        }
+        DamageKind::Blot if severity > 0 => {
+            let (centre_x, centre_y) = (rng.gen_range(0.0..n), rng.gen_range(0.0..n));
+
+            for y in 0..MODULE_COUNT {
+                for x in 0..MODULE_COUNT {
+                    let distance = (x as f64 + 0.5 - centre_x).hypot(y as f64 + 0.5 - centre_y);
+
+                    if distance <= severity as f64 {
+                        damaged.write(y, x, 1);
+                    }
+                }
+            }
+        }
+        DamageKind::Blot => {}
+        DamageKind::Tear => {
+            let (flip_x, flip_y) = (rng.gen_bool(0.5), rng.gen_bool(0.5));
+
+            for y in 0..MODULE_COUNT {
+                for x in 0..MODULE_COUNT {
+                    let dx = if flip_x { MODULE_COUNT - 1 - x } else { x };
+                    let dy = if flip_y { MODULE_COUNT - 1 - y } else { y };
+
+                    if dx + dy < severity as usize {
+                        damaged.write(y, x, 0);
+                    }
+                }
+            }
+        }
+    }
+
+    damaged
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrialResult {
+    pub version: &'static str,
+    pub ec_level: &'static str,
+    pub mask: u8,
+    pub damage: DamageKind,
+    pub severity: u32,
+    pub trials: u32,
+    /// Trials that decoded to the original text
+    pub decoded: u32,
+    /// Trials that decoded to different text without reporting an error
+    pub misread: u32,
+    /// Trials the decoder rejected
+    pub failed: u32,
+}
+
+impl TrialResult {
+    pub fn success_rate(&self) -> f64 {
+        self.decoded as f64 / self.trials as f64
+    }
+}
+
+pub struct Simulation<'a> {
+    pub text: &'a str,
+    pub damage: &'a [DamageKind],
+    /// Severities to try, or each damage kind's defaults when empty
+    pub severities: &'a [u32],
+    pub trials: u32,
+    pub seed: u64,
+}
+
+/// Runs every combination of mask, damage kind and severity, in that order.
+pub fn simulate(simulation: &Simulation, symbols: &[(u8, CodeMatrix)]) -> Vec<TrialResult> {
+    let mut rng = StdRng::seed_from_u64(simulation.seed);
+    let mut results = Vec::new();
+
+    for (mask, matrix) in symbols {
+        for &kind in simulation.damage {
+            let severities = match simulation.severities {
+                [] => kind.default_severities(),
+                severities => severities,
+            };
+
+            for &severity in severities {
+                let mut result = TrialResult {
+                    version: "M4",
+                    ec_level: "L",
+                    mask: *mask,
+                    damage: kind,
+                    severity,
+                    trials: simulation.trials,
+                    decoded: 0,
+                    misread: 0,
+                    failed: 0,
+                };
+
+                for _ in 0..simulation.trials {
+                    match decoder::decode(&apply(matrix, kind, severity, &mut rng)) {
+                        Ok(decoded) if decoded.text == simulation.text => result.decoded += 1,
+                        Ok(_) => result.misread += 1,
+                        Err(_) => result.failed += 1,
+                    }
+                }
+
+                results.push(result);
+            }
+        }
+    }
+
+    results
+}
+
+/// Success rates as a table, one row per damage kind and severity and one column per mask, followed
+/// by the misreads and failures of all masks together. A misread is a wrong payload returned as a
+/// valid read, a failure no read at all.
+pub fn table(results: &[TrialResult]) -> String {
+    let mut masks: Vec<u8> = results.iter().map(|result| result.mask).collect();
+    masks.sort();
+    masks.dedup();
+
+    let mut table = format!("{:<8} {:>8}", "Damage", "Severity");
+
+    for mask in &masks {
+        table.push_str(&format!("  {:>7}", format!("Mask {}", mask)));
+    }
+
+    table.push_str("  Misread   Failed\n");
+
+    let mut rows: Vec<(DamageKind, u32)> = Vec::new();
+
+    for result in results {
+        if !rows.contains(&(result.damage, result.severity)) {
+            rows.push((result.damage, result.severity));
+        }
+    }
+
+    for (kind, severity) in rows {
+        let row: Vec<&TrialResult> = results
+            .iter()
+            .filter(|result| result.damage == kind && result.severity == severity)
+            .collect();
+
+        table.push_str(&format!(
+            "{:<8} {:>8}",
+            format!("{:?}", kind).to_lowercase(),
+            severity
+        ));
+
+        for mask in &masks {
+            match row.iter().find(|result| result.mask == *mask) {
+                Some(result) => {
+                    table.push_str(&format!("  {:>6.1}%", result.success_rate() * 100.0))
+                }
+                None => table.push_str(&format!("  {:>7}", "-")),
+            }
+        }
+
+        let misread: u32 = row.iter().map(|result| result.misread).sum();
+        let failed: u32 = row.iter().map(|result| result.failed).sum();
+        table.push_str(&format!("  {:>7}  {:>7}\n", misread, failed));
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_mask::DataMask;
+    use crate::{data_codewords, symbol_matrix};
+
+    fn symbol(mask: u8) -> CodeMatrix {
+        symbol_matrix::generate(
+            &data_codewords::generate("DAMAGE"),
+            DataMask::from_pattern_ref(mask),
+        )
+    }
+
+    fn differences(a: &CodeMatrix, b: &CodeMatrix) -> usize {
+        (0..MODULE_COUNT)
+            .flat_map(|y| (0..MODULE_COUNT).map(move |x| (y, x)))
+            .filter(|&(y, x)| a.read(y, x) != b.read(y, x))
+            .count()
+    }
+
+    #[test]
+    fn test_flips_change_exact_module_count() {
+        let matrix = symbol(0);
+        let mut rng = StdRng::seed_from_u64(1);
+
+        assert_eq!(
+            differences(&matrix, &apply(&matrix, DamageKind::Flips, 7, &mut rng)),
+            7
+        );
+        assert_eq!(
+            differences(&matrix, &apply(&matrix, DamageKind::Flips, 0, &mut rng)),
+            0
+        );
+    }
+
+    #[test]
+    fn test_damage_only_clears_or_darkens() {
+        let matrix = symbol(1);
+        let mut rng = StdRng::seed_from_u64(2);
+
+        let torn = apply(&matrix, DamageKind::Tear, 5, &mut rng);
+        let blotted = apply(&matrix, DamageKind::Blot, 3, &mut rng);
+        let scratched = apply(&matrix, DamageKind::Scratch, 10, &mut rng);
+
+        for y in 0..MODULE_COUNT {
+            for x in 0..MODULE_COUNT {
+                assert!(torn.read(y, x) <= matrix.read(y, x));
+                assert!(scratched.read(y, x) <= matrix.read(y, x));
+                assert!(blotted.read(y, x) >= matrix.read(y, x));
+            }
+        }
+
+        assert!(differences(&matrix, &blotted) > 0);
+    }
+
+    #[test]
+    fn test_simulate_is_reproducible() {
+        let simulation = Simulation {
+            text: "DAMAGE",
+            damage: &[DamageKind::Flips],
+            severities: &[0, 30],
+            trials: 20,
+            seed: 7,
+        };
+        let symbols = [(2, symbol(2))];
+
+        let results = simulate(&simulation, &symbols);
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].decoded, 20);
+        assert!(results[1].decoded < 20);
+
+        // Every trial ends up in exactly one count
+        for result in &results {
+            assert_eq!(result.decoded + result.misread + result.failed, 20);
+        }
+        assert!(results[1].failed > 0);
+        assert_eq!(
+            simulate(&simulation, &symbols)[1].decoded,
+            results[1].decoded
+        );
+
+        let table = table(&results);
+        assert!(table.contains("Mask 2"));
+        assert!(table.contains("flips           0   100.0%        0        0"));
+    }
+}
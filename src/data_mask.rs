@@ -25,6 +25,25 @@ impl DataMask {
             .unwrap()
     }
 
+    /// The pattern score of the matrix under each mask, indexed by pattern reference.
+    pub fn scores(matrix: &CodeMatrix) -> [u16; 4] {
+        [0, 1, 2, 3].map(|pattern_ref| {
+            matrix
+                .with_data_mask(&Self::from_pattern_ref(pattern_ref).unwrap())
+                .pattern_score()
+        })
+    }
+
+    pub fn from_pattern_ref(pattern_reference: u8) -> Option<DataMask> {
+        match pattern_reference {
+            0 => Some(Self::pattern_00()),
+            1 => Some(Self::pattern_01()),
+            2 => Some(Self::pattern_10()),
+            3 => Some(Self::pattern_11()),
+            _ => None,
+        }
+    }
+
     pub fn new(pattern_reference: u8) -> Self {
         DataMask {
             data: [[0; 17]; 17],
@@ -228,6 +247,35 @@ mod tests {
         assert!(patterns_differ);
     }
 
+    #[test]
+    fn test_scores() {
+        let matrix = create_test_matrix();
+        let scores = DataMask::scores(&matrix);
+        let best = DataMask::best_pattern(&matrix).pattern_ref();
+
+        assert_eq!(scores[best as usize], *scores.iter().max().unwrap());
+        assert_eq!(
+            scores[1],
+            matrix
+                .with_data_mask(&DataMask::pattern_01())
+                .pattern_score()
+        );
+    }
+
+    #[test]
+    fn test_from_pattern_ref() {
+        for pattern_ref in 0..4 {
+            let mask = DataMask::from_pattern_ref(pattern_ref).unwrap();
+            assert_eq!(mask.pattern_ref(), pattern_ref);
+        }
+
+        assert_eq!(
+            DataMask::from_pattern_ref(2).unwrap().read_row(9),
+            DataMask::pattern_10().read_row(9)
+        );
+        assert!(DataMask::from_pattern_ref(4).is_none());
+    }
+
     #[test]
     fn test_pattern_00() {
         let pattern = DataMask::pattern_00();
@@ -0,0 +1,543 @@
+use crate::alphanumeric_mode;
+use crate::code_matrix::CodeMatrix;
+use crate::data_mask::DataMask;
+use crate::error_correction;
+use crate::format_info;
+use crate::gf_256::GF256;
+use bitstream_io::{BigEndian, BitRead, BitReader};
+
+/// Codeword capacity of a symbol type, from ISO/IEC 18004 Table 9.
+struct Capacity {
+    version: u32,
+    data_codewords: usize,
+    ec_codewords: usize,
+    /// Errors corrected, the rest of the error correction codewords guarding against misdecodes
+    max_correctable: usize,
+    /// Data capacity in bits, M1 and M3 symbols ending their data on a 4 bit codeword
+    data_bits: u32,
+}
+
+const fn capacity(
+    version: u32,
+    data_codewords: usize,
+    ec_codewords: usize,
+    max_correctable: usize,
+    data_bits: u32,
+) -> Capacity {
+    Capacity {
+        version,
+        data_codewords,
+        ec_codewords,
+        max_correctable,
+        data_bits,
+    }
+}
+
+// By symbol number, M1 to M4-Q. M1 symbols only detect errors.
+const CAPACITIES: [Capacity; 8] = [
+    capacity(1, 3, 2, 0, 20),
+    capacity(2, 5, 5, 1, 40),
+    capacity(2, 4, 6, 2, 32),
+    capacity(3, 11, 6, 2, 84),
+    capacity(3, 9, 8, 4, 68),
+    capacity(4, 16, 8, 3, 128),
+    capacity(4, 14, 10, 5, 112),
+    capacity(4, 10, 14, 7, 80),
+];
+
+pub const DATA_CODEWORDS: usize = CAPACITIES[format_info::M4_L as usize].data_codewords;
+
+/// Codewords an M4-L symbol corrects. ISO/IEC 18004 Table 9 gives (24, 16, 3): two of the eight
+/// error correction codewords are kept for misdecode protection, so a fourth error is rejected.
+pub const MAX_CORRECTABLE: usize = CAPACITIES[format_info::M4_L as usize].max_correctable;
+
+/// Data capacity of an M4-L symbol in bits
+pub const DATA_BITS: u32 = CAPACITIES[format_info::M4_L as usize].data_bits;
+
+// Mode indicators, written in one bit fewer than the version number
+const NUMERIC: u8 = 0b000;
+const ALPHANUMERIC: u8 = 0b001;
+const BYTE: u8 = 0b010;
+const KANJI: u8 = 0b011;
+
+// Character count indicator lengths for numeric, alphanumeric and byte mode, by version. M1 symbols
+// only hold numeric data and M2 symbols no byte data.
+const COUNT_BITS: [[u32; 3]; 4] = [[3, 0, 0], [4, 3, 0], [5, 4, 4], [6, 5, 5]];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoded {
+    pub text: String,
+    pub pattern_ref: u8,
+    /// Format information as read from the symbol, before any bit errors are corrected
+    pub format_info: u16,
+    /// Data codewords followed by error correction codewords, after correction
+    pub codewords: Vec<u8>,
+    pub errors_corrected: usize,
+    /// Bits taken by the encoded segments, not counting the terminator or padding
+    pub data_bits: u32,
+    /// Whether the symbol was read from its mirror image
+    pub mirrored: bool,
+}
+
+/// Reads the text from an M4-L symbol matrix, correcting errors where possible, and from its mirror
+/// image if the format information reads better that way.
+pub fn decode(matrix: &CodeMatrix) -> Result<Decoded, String> {
+    decode_versions(matrix, false)
+}
+
+/// Reads the text from a symbol of any Micro QR version, sitting in the top left corner of the
+/// matrix, as `decode` does for M4-L symbols.
+pub fn decode_any(matrix: &CodeMatrix) -> Result<Decoded, String> {
+    decode_versions(matrix, true)
+}
+
+fn decode_versions(matrix: &CodeMatrix, any_version: bool) -> Result<Decoded, String> {
+    let (matrix, mirrored) = unmirror(matrix);
+
+    // Damaged format information can look nearer to the wrong orientation, so try the other one
+    // before giving up, reporting why the likelier one failed
+    decode_oriented(&matrix, mirrored, any_version).or_else(|err| {
+        decode_oriented(&matrix.transposed(), !mirrored, any_version).map_err(|_| err)
+    })
+}
+
+fn decode_oriented(
+    matrix: &CodeMatrix,
+    mirrored: bool,
+    any_version: bool,
+) -> Result<Decoded, String> {
+    let format_info = matrix.read_format_info();
+
+    let (symbol_number, pattern_ref) = format_info::decode(format_info)
+        .ok_or_else(|| String::from("Format information is unreadable"))?;
+
+    if !any_version && symbol_number != format_info::M4_L {
+        return Err(format!(
+            "{} symbols are not supported, only M4-L",
+            format_info::SYMBOL_NAMES[symbol_number as usize]
+        ));
+    }
+
+    let capacity = &CAPACITIES[symbol_number as usize];
+    let size = format_info::SYMBOL_SIZES[symbol_number as usize];
+    let data_mask = DataMask::from_pattern_ref(pattern_ref).unwrap();
+    let mut codewords = matrix
+        .with_data_mask(&data_mask)
+        .read_codewords(size, &codeword_lengths(capacity));
+
+    let errors_corrected = error_correction::correct(
+        &mut codewords,
+        capacity.ec_codewords,
+        capacity.max_correctable,
+        &GF256::new(),
+    )?;
+    let (text, data_bits) = parse_segments(
+        &codewords[..capacity.data_codewords],
+        capacity.version,
+        capacity.data_bits,
+    )?;
+
+    Ok(Decoded {
+        text,
+        pattern_ref,
+        format_info,
+        codewords,
+        errors_corrected,
+        data_bits,
+        mirrored,
+    })
+}
+
+/// Bits in each codeword of a symbol, data then error correction.
+fn codeword_lengths(capacity: &Capacity) -> Vec<u32> {
+    let mut lengths = vec![8; capacity.data_codewords + capacity.ec_codewords];
+    lengths[capacity.data_codewords - 1] =
+        capacity.data_bits - 8 * (capacity.data_codewords as u32 - 1);
+
+    lengths
+}
+
+/// The likelier orientation of a matrix: as read, or transposed when the mirror image's format
+/// information is nearer a valid word, and whether it was transposed.
+///
+/// The format information of a mirror image read as it is can still come within 3 bits of some
+/// other valid word, so the two readings are compared rather than the mirror image only being
+/// tried when the first fails.
+pub fn unmirror(matrix: &CodeMatrix) -> (CodeMatrix, bool) {
+    let transposed = matrix.transposed();
+    let distance = |matrix: &CodeMatrix| format_info::distance(matrix.read_format_info());
+
+    match distance(&transposed) < distance(matrix) {
+        true => (transposed, true),
+        false => (matrix.clone(), false),
+    }
+}
+
+/// Decodes the first `total_bits` of the data bit stream of a symbol of the given version, segment
+/// by segment until the terminator or the end of the data.
+fn parse_segments(data: &[u8], version: u32, total_bits: u32) -> Result<(String, u32), String> {
+    let mut reader = BitReader::endian(data, BigEndian);
+    let mut text = String::new();
+    let mut position = 0;
+
+    let mode_bits = version - 1;
+    let terminator_bits = 2 * version + 1;
+    let count_bits = COUNT_BITS[version as usize - 1];
+    let truncated = |_| String::from("Data ends part way through a segment");
+
+    loop {
+        let remaining = total_bits - position;
+
+        // A terminator, or a truncated one at the very end of the data
+        if remaining < mode_bits.max(1) || peek(data, position, remaining.min(terminator_bits)) == 0
+        {
+            break;
+        }
+
+        // M1 symbols have no mode indicator, only holding numeric data
+        let mode: u8 = match mode_bits {
+            0 => NUMERIC,
+            _ => reader.read(mode_bits).map_err(truncated)?,
+        };
+        position += mode_bits;
+
+        let count_bits = match count_bits.get(mode as usize) {
+            Some(&bits) if bits > 0 => bits,
+            _ if mode == KANJI => return Err(String::from("Kanji mode is not supported")),
+            _ => {
+                return Err(format!(
+                    "Invalid mode indicator {:0width$b}",
+                    mode,
+                    width = mode_bits as usize
+                ))
+            }
+        };
+
+        match mode {
+            NUMERIC => {
+                let count: u32 = reader.read(count_bits).map_err(truncated)?;
+                position += count_bits;
+
+                let mut left = count;
+
+                while left > 0 {
+                    let (digits, bits) = match left {
+                        1 => (1, 4),
+                        2 => (2, 7),
+                        _ => (3, 10),
+                    };
+                    let value: u32 = reader.read(bits).map_err(truncated)?;
+                    position += bits;
+
+                    if value >= 10u32.pow(digits) {
+                        return Err(format!("Invalid numeric group {}", value));
+                    }
+
+                    text.push_str(&format!("{:0width$}", value, width = digits as usize));
+                    left -= digits;
+                }
+            }
+            ALPHANUMERIC => {
+                let count: u32 = reader.read(count_bits).map_err(truncated)?;
+                position += count_bits;
+
+                let charset = alphanumeric_mode::CHARSET.as_bytes();
+                let mut left = count;
+
+                while left > 0 {
+                    if left == 1 {
+                        let value: u32 = reader.read(6).map_err(truncated)?;
+                        position += 6;
+
+                        let c = charset
+                            .get(value as usize)
+                            .ok_or("Invalid alphanumeric character")?;
+                        text.push(*c as char);
+                        left -= 1;
+                    } else {
+                        let value: u32 = reader.read(11).map_err(truncated)?;
+                        let value = value as usize;
+                        position += 11;
+
+                        if value >= 45 * 45 {
+                            return Err(format!("Invalid alphanumeric pair {}", value));
+                        }
+
+                        text.push(charset[value / 45] as char);
+                        text.push(charset[value % 45] as char);
+                        left -= 2;
+                    }
+                }
+            }
+            BYTE => {
+                let count: u32 = reader.read(count_bits).map_err(truncated)?;
+                position += count_bits;
+
+                for _ in 0..count {
+                    let byte: u8 = reader.read(8).map_err(truncated)?;
+                    position += 8;
+
+                    // Byte mode defaults to ISO 8859-1, which maps straight onto Unicode
+                    text.push(byte as char);
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    Ok((text, position))
+}
+
+fn peek(data: &[u8], position: u32, bits: u32) -> u32 {
+    (position..position + bits).fold(0, |acc, bit| {
+        let byte = data[(bit / 8) as usize];
+        (acc << 1) | ((byte >> (7 - bit % 8)) & 1) as u32
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_matrix::{self, ModuleRole};
+    use crate::{data_codewords, symbol_matrix};
+
+    fn encode(text: &str, pattern_ref: Option<u8>) -> CodeMatrix {
+        symbol_matrix::generate(
+            &data_codewords::generate(text),
+            pattern_ref.and_then(DataMask::from_pattern_ref),
+        )
+    }
+
+    #[test]
+    fn test_decode_generated_symbols() {
+        for text in ["", "A", "HELLO WORLD", "0123456789ABCDEFGHIJK", "$%*+-./: "] {
+            for pattern_ref in 0..4 {
+                let decoded = decode(&encode(text, Some(pattern_ref))).unwrap();
+
+                assert_eq!(decoded.text, text);
+                assert_eq!(decoded.pattern_ref, pattern_ref);
+                assert_eq!(decoded.errors_corrected, 0);
+                assert_eq!(decoded.codewords, data_codewords::generate(text));
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_data_bits() {
+        // Mode and count indicators, then two 11 bit pairs and a 6 bit single character
+        assert_eq!(
+            decode(&encode("HELLO", None)).unwrap().data_bits,
+            3 + 5 + 22 + 6
+        );
+    }
+
+    #[test]
+    fn test_decode_corrects_damaged_modules() {
+        let mut matrix = encode("DAMAGED", Some(1));
+
+        // Flip every module of two codeword blocks
+        for y in 13..17 {
+            for x in 13..17 {
+                matrix.write(y, x, 1 - matrix.read(y, x));
+            }
+        }
+
+        let decoded = decode(&matrix).unwrap();
+
+        assert_eq!(decoded.text, "DAMAGED");
+        assert_eq!(decoded.errors_corrected, 2);
+    }
+
+    /// The symbol with one bit flipped in each of the first `count` codewords.
+    fn damage_codewords(matrix: &CodeMatrix, count: usize) -> CodeMatrix {
+        let mut damaged = matrix.clone();
+
+        for y in 0..17 {
+            for x in 0..17 {
+                if let ModuleRole::Codeword { index, bit: 0 } = code_matrix::module_role(y, x) {
+                    if index < count {
+                        damaged.write(y, x, 1 - damaged.read(y, x));
+                    }
+                }
+            }
+        }
+
+        damaged
+    }
+
+    #[test]
+    fn test_decode_rejects_more_than_three_codeword_errors() {
+        let matrix = encode("LIMIT", Some(2));
+
+        assert_eq!(
+            decode(&damage_codewords(&matrix, 3))
+                .unwrap()
+                .errors_corrected,
+            3
+        );
+        assert!(decode(&damage_codewords(&matrix, 4)).is_err());
+    }
+
+    #[test]
+    fn test_decode_mirrored_symbols() {
+        for pattern_ref in 0..4 {
+            let matrix = encode("MIRROR", Some(pattern_ref));
+
+            assert!(!decode(&matrix).unwrap().mirrored);
+
+            let decoded = decode(&matrix.transposed()).unwrap();
+
+            assert_eq!(decoded.text, "MIRROR");
+            assert_eq!(decoded.pattern_ref, pattern_ref);
+            assert!(decoded.mirrored);
+        }
+    }
+
+    #[test]
+    fn test_decode_damaged_format_info_in_either_orientation() {
+        let mut matrix = encode("MIRROR", Some(0));
+
+        // Two flipped format bits bring the mirror image's reading nearer a valid word
+        for (y, x) in [(8, 4), (8, 3)] {
+            matrix.write(y, x, 1 - matrix.read(y, x));
+        }
+        assert!(unmirror(&matrix).1);
+
+        let decoded = decode(&matrix).unwrap();
+
+        assert_eq!(decoded.text, "MIRROR");
+        assert!(!decoded.mirrored);
+
+        let decoded = decode(&matrix.transposed()).unwrap();
+
+        assert_eq!(decoded.text, "MIRROR");
+        assert!(decoded.mirrored);
+    }
+
+    /// A symbol of any version holding the given bit fields, zero filled to its data capacity.
+    fn encode_version(symbol_number: u8, pattern_ref: u8, fields: &[(u32, u32)]) -> CodeMatrix {
+        use bitstream_io::{BitWrite, BitWriter};
+
+        let capacity = &CAPACITIES[symbol_number as usize];
+        let mut data = Vec::new();
+        let mut writer = BitWriter::endian(&mut data, BigEndian);
+
+        for &(bits, value) in fields {
+            writer.write(bits, value).unwrap();
+        }
+        writer.byte_align().unwrap();
+        data.resize(capacity.data_codewords, 0);
+
+        let gf = GF256::new();
+        let ec = error_correction::calculate_codewords(
+            &data,
+            &error_correction::generator(capacity.ec_codewords, &gf),
+            &gf,
+            capacity.ec_codewords,
+        );
+
+        CodeMatrix::with_codewords(
+            format_info::SYMBOL_SIZES[symbol_number as usize],
+            &codeword_lengths(capacity),
+            &[data, ec].concat(),
+        )
+        .with_data_mask(&DataMask::from_pattern_ref(pattern_ref).unwrap())
+        .with_format_info(format_info::bch_word(symbol_number, pattern_ref))
+    }
+
+    #[test]
+    fn test_decode_every_version() {
+        type Fields = &'static [(u32, u32)];
+
+        let symbols: [(u8, Fields, &str); 7] = [
+            // M1 numeric "12345", filling the symbol with no room for a terminator
+            (0, &[(3, 5), (10, 123), (7, 45)], "12345"),
+            // M2-L alphanumeric "AB1"
+            (1, &[(1, 1), (3, 3), (11, 461), (6, 1)], "AB1"),
+            // M2-M numeric "0123"
+            (2, &[(1, 0), (4, 4), (10, 12), (4, 3)], "0123"),
+            // M3-L byte "hi"
+            (3, &[(2, 2), (4, 2), (8, 0x68), (8, 0x69)], "hi"),
+            // M3-M numeric "7"
+            (4, &[(2, 0), (5, 1), (4, 7)], "7"),
+            // M4-M alphanumeric "MICRO"
+            (
+                6,
+                &[(3, 1), (5, 5), (11, 1008), (11, 567), (6, 24)],
+                "MICRO",
+            ),
+            // M4-Q byte "ok"
+            (7, &[(3, 2), (5, 2), (8, 0x6f), (8, 0x6b)], "ok"),
+        ];
+
+        for (symbol_number, fields, text) in symbols {
+            for pattern_ref in 0..4 {
+                let matrix = encode_version(symbol_number, pattern_ref, fields);
+                let decoded = decode_any(&matrix).unwrap();
+
+                assert_eq!(decoded.text, text);
+                assert_eq!(decoded.pattern_ref, pattern_ref);
+                assert_eq!(decode_any(&matrix.transposed()).unwrap().text, text);
+                assert!(decode(&matrix).unwrap_err().contains("not supported"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_any_corrects_as_many_errors_as_the_version_allows() {
+        for (symbol_number, max_correctable) in [(0, 0), (1, 1), (4, 4), (7, 7)] {
+            let matrix = encode_version(symbol_number, 1, &[]);
+            let size = format_info::SYMBOL_SIZES[symbol_number as usize];
+            let lengths = codeword_lengths(&CAPACITIES[symbol_number as usize]);
+
+            // Flip the first module of each of the first `count` codewords
+            let damage = |count: usize| {
+                let mut damaged = matrix.clone();
+                let modules = code_matrix::data_modules(size);
+
+                for first in lengths.iter().take(count).scan(0, |bit, &length| {
+                    *bit += length as usize;
+                    Some(*bit - length as usize)
+                }) {
+                    let (y, x) = modules[first];
+                    damaged.write(y, x, 1 - damaged.read(y, x));
+                }
+
+                damaged
+            };
+
+            let decoded = decode_any(&damage(max_correctable)).unwrap();
+
+            assert_eq!(decoded.text, "");
+            assert_eq!(decoded.errors_corrected, max_correctable);
+            assert!(decode_any(&damage(max_correctable + 1)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_decode_rejects_blank_matrix() {
+        assert!(decode(&CodeMatrix::new()).is_err());
+    }
+
+    #[test]
+    fn test_parse_numeric_and_byte_segments() {
+        // Numeric "123" then byte "a", then the terminator
+        let mut data = Vec::new();
+        let mut writer = bitstream_io::BitWriter::endian(&mut data, BigEndian);
+
+        use bitstream_io::BitWrite;
+        writer.write(3, NUMERIC).unwrap();
+        writer.write(6, 3u8).unwrap();
+        writer.write(10, 123u16).unwrap();
+        writer.write(3, BYTE).unwrap();
+        writer.write(5, 1u8).unwrap();
+        writer.write(8, b'a').unwrap();
+        writer.write(9, 0u16).unwrap();
+        writer.byte_align().unwrap();
+
+        assert_eq!(
+            parse_segments(&data, 4, data.len() as u32 * 8).unwrap(),
+            (String::from("123a"), 3 + 6 + 10 + 3 + 5 + 8)
+        );
+    }
+}
@@ -0,0 +1,338 @@
+//! Simulated capture of a rendered symbol, with perspective skew, rotation, uneven illumination, blur,
+//! sensor noise and JPEG compression, and the ground truth needed to check a reader against it.
+
+use crate::perspective::{self, Homography, Point};
+use crate::symbol_data::SymbolData;
+use image::codecs::jpeg::JpegEncoder;
+use image::{ExtendedColorType, GrayImage, Luma, Rgba, RgbaImage};
+use rand::Rng;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Degradation {
+    /// Gaussian blur standard deviation in pixels
+    pub blur: f64,
+    /// Gaussian noise standard deviation in grey levels
+    pub noise: f64,
+    /// Largest corner displacement of the perspective skew, as a fraction of the image side
+    pub skew: f64,
+    /// Clockwise rotation in degrees
+    pub rotation: f64,
+    /// Brightness lost from one side of the image to the other, from 0 (even) to 1 (black)
+    pub illumination: f64,
+    /// JPEG quality from 1 to 100, or none for no compression
+    pub jpeg_quality: Option<u8>,
+}
+
+impl Degradation {
+    /// Draws every parameter at random between none and this one, used as the limit. Rotation goes
+    /// either way and JPEG quality lies between this one and 100.
+    pub fn sample(&self, rng: &mut impl Rng) -> Self {
+        Self {
+            blur: upto(rng, self.blur),
+            noise: upto(rng, self.noise),
+            skew: upto(rng, self.skew),
+            rotation: match self.rotation > 0.0 {
+                true => rng.gen_range(-self.rotation..=self.rotation),
+                false => 0.0,
+            },
+            illumination: upto(rng, self.illumination),
+            jpeg_quality: self
+                .jpeg_quality
+                .map(|quality| rng.gen_range(quality..=100)),
+        }
+    }
+}
+
+fn upto(rng: &mut impl Rng, limit: f64) -> f64 {
+    match limit > 0.0 {
+        true => rng.gen_range(0.0..=limit),
+        false => 0.0,
+    }
+}
+
+pub struct Capture {
+    pub image: GrayImage,
+    /// Top left, top right, bottom right and bottom left corners of the symbol, without its
+    /// quiet zone, in the captured image
+    pub corners: [Point; 4],
+    /// Direction the light falls off towards, in degrees clockwise from the positive x axis
+    pub illumination_angle: f64,
+}
+
+/// Where the symbol ended up in a captured image, and how it got there.
+#[derive(Debug, Serialize)]
+pub struct GroundTruth {
+    pub file: String,
+    #[serde(flatten)]
+    pub symbol: SymbolData,
+    pub module_size: u32,
+    pub quiet_zone: u32,
+    pub degradation: Degradation,
+    pub illumination_angle: f64,
+    pub corners: [Point; 4],
+}
+
+/// Applies the degradation to a rendered image, whose symbol has the given corners. The image is
+/// converted to grey, and enlarged where needed so that nothing is turned out of view. Fails if the
+/// skew folds the image over itself, or if JPEG compression fails.
+pub fn capture(
+    img: &RgbaImage,
+    symbol: [Point; 4],
+    degradation: &Degradation,
+    rng: &mut impl Rng,
+) -> Result<Capture, String> {
+    let (width, height) = (img.width() as f64, img.height() as f64);
+    let outline = [(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)];
+
+    // Rotate the outline about its centre, then pull each corner a random way for the skew
+    let (sin, cos) = degradation.rotation.to_radians().sin_cos();
+    let reach = degradation.skew * width.max(height);
+
+    let moved = outline.map(|(x, y)| {
+        let (dx, dy) = (x - width / 2.0, y - height / 2.0);
+
+        (
+            dx * cos - dy * sin + rng.gen_range(-1.0..=1.0) * reach,
+            dx * sin + dy * cos + rng.gen_range(-1.0..=1.0) * reach,
+        )
+    });
+
+    let left = moved.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+    let top = moved.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let right = moved.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+    let bottom = moved.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    let moved = moved.map(|(x, y)| (x - left, y - top));
+
+    let forward = Homography::from_quad(outline, moved)
+        .ok_or_else(|| String::from("The skewed corners do not form a quadrilateral"))?;
+    let backward = forward
+        .inverse()
+        .ok_or_else(|| String::from("The skewed perspective cannot be inverted"))?;
+
+    let grey = grey_levels(img);
+    let background = grey_level(*img.get_pixel(0, 0));
+    let (out_width, out_height) = ((right - left).ceil() as u32, (bottom - top).ceil() as u32);
+    let mut levels = vec![0.0; (out_width * out_height) as usize];
+
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let source = backward.map((x as f64 + 0.5, y as f64 + 0.5));
+            levels[(y * out_width + x) as usize] =
+                perspective::interpolate(&grey, img.width(), img.height(), source)
+                    .unwrap_or(background);
+        }
+    }
+
+    let illumination_angle = rng.gen_range(0.0..360.0);
+    illuminate(
+        &mut levels,
+        out_width,
+        out_height,
+        degradation.illumination,
+        illumination_angle,
+    );
+
+    let mut levels = blur(&levels, out_width, out_height, degradation.blur);
+
+    if degradation.noise > 0.0 {
+        for level in levels.iter_mut() {
+            *level += gaussian(rng) * degradation.noise;
+        }
+    }
+
+    let mut image = GrayImage::from_fn(out_width, out_height, |x, y| {
+        Luma([levels[(y * out_width + x) as usize]
+            .round()
+            .clamp(0.0, 255.0) as u8])
+    });
+
+    if let Some(quality) = degradation.jpeg_quality {
+        let mut bytes = Vec::new();
+        JpegEncoder::new_with_quality(&mut bytes, quality)
+            .encode(&image, out_width, out_height, ExtendedColorType::L8)
+            .map_err(|err| err.to_string())?;
+        image = image::load_from_memory(&bytes)
+            .map_err(|err| err.to_string())?
+            .to_luma8();
+    }
+
+    Ok(Capture {
+        image,
+        corners: symbol.map(|corner| forward.map(corner)),
+        illumination_angle,
+    })
+}
+
+fn grey_level(pixel: Rgba<u8>) -> f64 {
+    let [r, g, b, _] = pixel.0.map(f64::from);
+    0.299 * r + 0.587 * g + 0.114 * b
+}
+
+fn grey_levels(img: &RgbaImage) -> Vec<f64> {
+    img.pixels().map(|pixel| grey_level(*pixel)).collect()
+}
+
+/// Darkens the image linearly towards the side the angle points to.
+fn illuminate(levels: &mut [f64], width: u32, height: u32, strength: f64, angle: f64) {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let corners = [
+        (0.0, 0.0),
+        (width as f64, 0.0),
+        (0.0, height as f64),
+        (width as f64, height as f64),
+    ];
+    let along = |(x, y): (f64, f64)| x * cos + y * sin;
+    let low = corners.map(along).into_iter().fold(f64::INFINITY, f64::min);
+    let high = corners
+        .map(along)
+        .into_iter()
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    for y in 0..height {
+        for x in 0..width {
+            let t = (along((x as f64 + 0.5, y as f64 + 0.5)) - low) / (high - low);
+            levels[(y * width + x) as usize] *= 1.0 - strength * t;
+        }
+    }
+}
+
+/// Separable Gaussian blur, repeating the edge pixels beyond the image.
+fn blur(levels: &[f64], width: u32, height: u32, sigma: f64) -> Vec<f64> {
+    if sigma <= 0.0 {
+        return levels.to_vec();
+    }
+
+    let radius = (3.0 * sigma).ceil() as i64;
+    let kernel: Vec<f64> = (-radius..=radius)
+        .map(|i| (-(i * i) as f64 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f64 = kernel.iter().sum();
+    let kernel: Vec<f64> = kernel.iter().map(|weight| weight / total).collect();
+
+    let (width, height) = (width as i64, height as i64);
+    let pass = |levels: &[f64], dx: i64, dy: i64| -> Vec<f64> {
+        let mut out = vec![0.0; levels.len()];
+
+        for y in 0..height {
+            for x in 0..width {
+                out[(y * width + x) as usize] = kernel
+                    .iter()
+                    .enumerate()
+                    .map(|(i, weight)| {
+                        let offset = i as i64 - radius;
+                        let sx = (x + offset * dx).clamp(0, width - 1);
+                        let sy = (y + offset * dy).clamp(0, height - 1);
+                        levels[(sy * width + sx) as usize] * weight
+                    })
+                    .sum();
+            }
+        }
+
+        out
+    };
+
+    pass(&pass(levels, 1, 0), 0, 1)
+}
+
+/// Standard normal deviate by the Box-Muller transform.
+fn gaussian(rng: &mut impl Rng) -> f64 {
+    let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+    let v: f64 = rng.gen_range(0.0..1.0);
+
+    (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbol_image::{self, ImageOptions};
+    use crate::{data_codewords, symbol_matrix, symbol_reader};
+    use image::buffer::ConvertBuffer;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const NONE: Degradation = Degradation {
+        blur: 0.0,
+        noise: 0.0,
+        skew: 0.0,
+        rotation: 0.0,
+        illumination: 0.0,
+        jpeg_quality: None,
+    };
+
+    fn rendered() -> (RgbaImage, [Point; 4]) {
+        let matrix = symbol_matrix::generate(&data_codewords::generate("CAPTURE"), None);
+        let img = symbol_image::render(&ImageOptions::new(8), &matrix);
+        let corners = [(16.0, 16.0), (152.0, 16.0), (152.0, 152.0), (16.0, 152.0)];
+
+        (img, corners)
+    }
+
+    #[test]
+    fn test_no_degradation() {
+        let (img, corners) = rendered();
+        let capture = capture(&img, corners, &NONE, &mut StdRng::seed_from_u64(1)).unwrap();
+
+        assert_eq!(capture.image.dimensions(), img.dimensions());
+        for (corner, expected) in capture.corners.iter().zip(corners) {
+            assert!((corner.0 - expected.0).abs() < 1e-6 && (corner.1 - expected.1).abs() < 1e-6);
+        }
+
+        assert_eq!(capture.image.get_pixel(20, 20).0, [0]);
+        assert_eq!(capture.image.get_pixel(4, 4).0, [255]);
+    }
+
+    #[test]
+    fn test_rotation_corners() {
+        let (img, corners) = rendered();
+        let degradation = Degradation {
+            rotation: 90.0,
+            ..NONE
+        };
+        let capture = capture(&img, corners, &degradation, &mut StdRng::seed_from_u64(1)).unwrap();
+
+        // A quarter turn clockwise puts the top left corner at the top right
+        let (x, y) = capture.corners[0];
+        assert!((x - 152.0).abs() < 1e-6 && (y - 16.0).abs() < 1e-6);
+        assert_eq!(capture.image.get_pixel(150, 20).0, [0]);
+    }
+
+    #[test]
+    fn test_mild_capture_still_decodes() {
+        let (img, corners) = rendered();
+        let degradation = Degradation {
+            blur: 1.0,
+            illumination: 0.2,
+            jpeg_quality: Some(60),
+            ..NONE
+        };
+        let capture = capture(&img, corners, &degradation, &mut StdRng::seed_from_u64(3)).unwrap();
+
+        let decoded = symbol_reader::decode_image(&capture.image.convert()).unwrap();
+        assert_eq!(decoded.text, "CAPTURE");
+    }
+
+    #[test]
+    fn test_sample_within_limits() {
+        let limits = Degradation {
+            blur: 2.0,
+            noise: 20.0,
+            skew: 0.1,
+            rotation: 15.0,
+            illumination: 0.5,
+            jpeg_quality: Some(40),
+        };
+        let mut rng = StdRng::seed_from_u64(9);
+
+        for _ in 0..50 {
+            let sample = limits.sample(&mut rng);
+
+            assert!((0.0..=2.0).contains(&sample.blur));
+            assert!((-15.0..=15.0).contains(&sample.rotation));
+            assert!((40..=100).contains(&sample.jpeg_quality.unwrap()));
+        }
+
+        assert_eq!(NONE.sample(&mut rng), NONE);
+    }
+}
@@ -27,10 +27,190 @@ pub fn calculate_codewords(
     registers
 }
 
+/// Coefficients of the generator polynomial for `number_of_codewords` error correction codewords,
+/// highest power first, as `calculate_codewords` takes them.
+pub fn generator(number_of_codewords: usize, galois_field: &GF256) -> Vec<u8> {
+    (0..number_of_codewords).fold(vec![1], |polynomial, i| {
+        // Multiply by (x - a^i)
+        let root = galois_field.power(i) as usize;
+        let mut product = polynomial.clone();
+        product.push(0);
+
+        for (j, &coefficient) in polynomial.iter().enumerate() {
+            product[j + 1] ^= galois_field.multiply(coefficient as usize, root);
+        }
+
+        product
+    })
+}
+
+/// Corrects a block of data codewords followed by its error correction codewords in place, returning
+/// how many codewords were wrong. Up to `max_errors` errors are corrected, which can be no more than
+/// half as many as there are error correction codewords.
+pub fn correct(
+    codewords: &mut [u8],
+    number_of_codewords: usize,
+    max_errors: usize,
+    galois_field: &GF256,
+) -> Result<usize, String> {
+    let syndromes = syndromes(codewords, number_of_codewords, galois_field);
+
+    if syndromes.iter().all(|&s| s == 0) {
+        return Ok(0);
+    }
+
+    let locator = error_locator(&syndromes, galois_field);
+    let error_count = locator.len() - 1;
+
+    if error_count > max_errors.min(number_of_codewords / 2) {
+        return Err(String::from("Too many errors to correct"));
+    }
+
+    // Chien search: the locator has a root at the inverse of every error position
+    let length = codewords.len();
+    let positions: Vec<usize> = (0..length)
+        .filter(|&k| {
+            let inverse = galois_field.power(255 - (length - 1 - k) % 255);
+            evaluate_low_first(&locator, inverse, galois_field) == 0
+        })
+        .collect();
+
+    if positions.len() != error_count {
+        return Err(String::from("Too many errors to correct"));
+    }
+
+    // Forney: error magnitudes from the evaluator and the formal derivative of the locator
+    let mut evaluator = vec![0; number_of_codewords];
+
+    for (i, &s) in syndromes.iter().enumerate() {
+        for (j, &l) in locator.iter().enumerate() {
+            if i + j < number_of_codewords {
+                evaluator[i + j] ^= galois_field.multiply(s as usize, l as usize);
+            }
+        }
+    }
+
+    let derivative: Vec<u8> = locator
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, &l)| if i % 2 == 1 { l } else { 0 })
+        .collect();
+
+    for &k in &positions {
+        let position = galois_field.power(length - 1 - k);
+        let inverse = galois_field.power(255 - (length - 1 - k) % 255);
+
+        let numerator = evaluate_low_first(&evaluator, inverse, galois_field);
+        let denominator = evaluate_low_first(&derivative, inverse, galois_field);
+
+        if denominator == 0 {
+            return Err(String::from("Too many errors to correct"));
+        }
+
+        let magnitude = galois_field.divide(
+            galois_field.multiply(position as usize, numerator as usize) as usize,
+            denominator as usize,
+        );
+
+        codewords[k] ^= magnitude;
+    }
+
+    if syndromes_are_zero(codewords, number_of_codewords, galois_field) {
+        Ok(error_count)
+    } else {
+        Err(String::from("Too many errors to correct"))
+    }
+}
+
+/// The received block evaluated at each root of the generator polynomial, all zero when intact.
+fn syndromes(codewords: &[u8], number_of_codewords: usize, galois_field: &GF256) -> Vec<u8> {
+    (0..number_of_codewords)
+        .map(|i| {
+            let root = galois_field.power(i) as usize;
+
+            // Horner's method, the first codeword being the highest power
+            codewords.iter().fold(0, |acc, &codeword| {
+                galois_field.multiply(acc as usize, root) ^ codeword
+            })
+        })
+        .collect()
+}
+
+fn syndromes_are_zero(codewords: &[u8], number_of_codewords: usize, galois_field: &GF256) -> bool {
+    syndromes(codewords, number_of_codewords, galois_field)
+        .iter()
+        .all(|&s| s == 0)
+}
+
+/// Berlekamp-Massey, giving the error locator polynomial lowest power first.
+fn error_locator(syndromes: &[u8], galois_field: &GF256) -> Vec<u8> {
+    let mut locator = vec![1];
+    let mut previous = vec![1];
+    let mut length = 0;
+    let mut shift = 1;
+    let mut previous_discrepancy = 1;
+
+    for k in 0..syndromes.len() {
+        let mut discrepancy = syndromes[k];
+
+        for i in 1..=length.min(locator.len() - 1) {
+            discrepancy ^= galois_field.multiply(locator[i] as usize, syndromes[k - i] as usize);
+        }
+
+        if discrepancy == 0 {
+            shift += 1;
+            continue;
+        }
+
+        let scale = galois_field.divide(discrepancy as usize, previous_discrepancy as usize);
+        let mut next = locator.clone();
+
+        if next.len() < previous.len() + shift {
+            next.resize(previous.len() + shift, 0);
+        }
+
+        for (i, &p) in previous.iter().enumerate() {
+            next[i + shift] ^= galois_field.multiply(scale as usize, p as usize);
+        }
+
+        if 2 * length <= k {
+            previous = locator;
+            length = k + 1 - length;
+            previous_discrepancy = discrepancy;
+            shift = 1;
+        } else {
+            shift += 1;
+        }
+
+        locator = next;
+    }
+
+    locator.truncate(length + 1);
+    locator
+}
+
+fn evaluate_low_first(polynomial: &[u8], x: u8, galois_field: &GF256) -> u8 {
+    polynomial.iter().rev().fold(0, |acc, &coefficient| {
+        galois_field.multiply(acc as usize, x as usize) ^ coefficient
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_generator() {
+        let gf = GF256::new();
+
+        assert_eq!(generator(5, &gf), [0x01, 0x1f, 0xc6, 0x3f, 0x93, 0x74]);
+        assert_eq!(
+            generator(8, &gf),
+            [0x01, 0xff, 0x0b, 0x51, 0x36, 0xef, 0xad, 0xc8, 0x18]
+        );
+    }
+
     #[test]
     fn test_5_codewords() {
         let result = calculate_codewords(
@@ -77,4 +257,62 @@ mod tests {
         assert_eq!(result[8], 0b00101100);
         assert_eq!(result[9], 0b01010101);
     }
+
+    fn test_block() -> Vec<u8> {
+        let data = [
+            0x10, 0x20, 0x0C, 0x56, 0x61, 0x80, 0xEC, 0x11, 0xEC, 0x11, 0xEC, 0x11, 0xEC, 0x11,
+            0xEC, 0x11,
+        ];
+        let coefficients = [
+            0x01, 0xD8, 0xC2, 0x9F, 0x6F, 0xC7, 0x5E, 0x5F, 0x71, 0x9D, 0xC1,
+        ];
+        let ec_codewords = calculate_codewords(&data, &coefficients, &GF256::new(), 10);
+
+        [&data[..], &ec_codewords].concat()
+    }
+
+    #[test]
+    fn test_correct_intact_block() {
+        let mut block = test_block();
+
+        assert_eq!(correct(&mut block, 10, 5, &GF256::new()), Ok(0));
+        assert_eq!(block, test_block());
+    }
+
+    #[test]
+    fn test_correct_up_to_half_the_ec_codewords() {
+        let mut block = test_block();
+
+        // Errors in both the data and the error correction codewords
+        for (position, error) in [(0, 0xFF), (3, 0x01), (9, 0x5A), (17, 0x80), (25, 0x33)] {
+            block[position] ^= error;
+        }
+
+        assert_eq!(correct(&mut block, 10, 5, &GF256::new()), Ok(5));
+        assert_eq!(block, test_block());
+    }
+
+    #[test]
+    fn test_correct_reports_too_many_errors() {
+        let mut block = test_block();
+
+        for position in [0, 2, 4, 6, 8, 10] {
+            block[position] ^= 0xA5;
+        }
+
+        assert!(correct(&mut block, 10, 5, &GF256::new()).is_err());
+    }
+
+    #[test]
+    fn test_correct_stops_at_max_errors() {
+        let mut block = test_block();
+
+        for position in [1, 5, 12] {
+            block[position] ^= 0x3C;
+        }
+
+        assert!(correct(&mut block.clone(), 10, 2, &GF256::new()).is_err());
+        assert_eq!(correct(&mut block, 10, 3, &GF256::new()), Ok(3));
+        assert_eq!(block, test_block());
+    }
 }
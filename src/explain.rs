@@ -0,0 +1,260 @@
+//! A readable trace of every encoding stage, for working out why a symbol does not scan.
+
+use crate::code_matrix::CodeMatrix;
+use crate::data_mask::DataMask;
+use crate::pattern_scoring::PatternScoring;
+use crate::{alphanumeric_mode, data_codewords, format_info};
+use std::fmt::Write;
+
+const DATA_CODEWORDS: usize = 16;
+const DATA_BITS: usize = DATA_CODEWORDS * 8;
+const TERMINATOR_BITS: usize = 9;
+
+/// Traces the encoding of a validated input string, with the mask that `symbol_matrix::generate`
+/// would use for the same pattern reference.
+pub fn explain(input: &str, pattern_ref: Option<u8>) -> String {
+    let codewords = data_codewords::generate(input);
+    let (data, ec) = codewords.split_at(DATA_CODEWORDS);
+    let bits = Bits(data);
+    let mut out = String::new();
+
+    writeln!(out, "Input: \"{}\" ({} characters)", input, input.len()).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "Data bit stream").unwrap();
+
+    let mut position = 0;
+    field(
+        &mut out,
+        &bits,
+        &mut position,
+        "Mode indicator",
+        3,
+        String::from("alphanumeric"),
+    );
+    field(
+        &mut out,
+        &bits,
+        &mut position,
+        "Character count",
+        5,
+        input.len().to_string(),
+    );
+
+    for group in input.as_bytes().chunks(2) {
+        let values: Vec<u16> = group
+            .iter()
+            .map(|&c| alphanumeric_mode::get_alphanumeric_value(c as char).unwrap() as u16)
+            .collect();
+        let text = String::from_utf8_lossy(group);
+
+        match values[..] {
+            [first, second] => field(
+                &mut out,
+                &bits,
+                &mut position,
+                &format!("Characters \"{}\"", text),
+                11,
+                format!("{} x 45 + {} = {}", first, second, first * 45 + second),
+            ),
+            _ => field(
+                &mut out,
+                &bits,
+                &mut position,
+                &format!("Character \"{}\"", text),
+                6,
+                values[0].to_string(),
+            ),
+        }
+    }
+
+    // The terminator is cut short when fewer than 9 bits are left
+    let terminator = TERMINATOR_BITS.min(DATA_BITS - position);
+
+    if terminator > 0 {
+        let note = match terminator {
+            TERMINATOR_BITS => String::new(),
+            _ => String::from("truncated at the end of the data"),
+        };
+
+        field(
+            &mut out,
+            &bits,
+            &mut position,
+            "Terminator",
+            terminator,
+            note,
+        );
+    }
+
+    let padding = (8 - position % 8) % 8;
+
+    if padding > 0 {
+        field(
+            &mut out,
+            &bits,
+            &mut position,
+            "Padding bits",
+            padding,
+            String::from("to a codeword boundary"),
+        );
+    }
+
+    let pad_codewords = &data[position / 8..];
+
+    let pad_codewords = match pad_codewords.len() {
+        0 => String::from("none"),
+        _ => hex(pad_codewords),
+    };
+
+    writeln!(out, "  {:<20} {}", "Pad codewords", pad_codewords).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "Data codewords         {}", hex(data)).unwrap();
+    writeln!(out, "EC codewords           {}", hex(ec)).unwrap();
+    writeln!(out).unwrap();
+
+    let data_matrix = CodeMatrix::with_data(&codewords);
+    let best = DataMask::best_pattern(&data_matrix).pattern_ref();
+    let selected = pattern_ref.unwrap_or(best);
+
+    writeln!(
+        out,
+        "Mask patterns (score = smaller edge sum x 16 + larger edge sum, highest wins, ties go to the later pattern)"
+    )
+    .unwrap();
+
+    for candidate in 0..4 {
+        let masked = data_matrix.with_data_mask(&DataMask::from_pattern_ref(candidate).unwrap());
+        let note = match (candidate == selected, candidate == best) {
+            (true, true) => "  selected",
+            (true, false) => "  forced",
+            (false, true) => "  best",
+            (false, false) => "",
+        };
+
+        writeln!(
+            out,
+            "  {}: right edge {:>2}, bottom edge {:>2}, score {:>3}{}",
+            candidate,
+            masked.count_right_edge(),
+            masked.count_bottom_edge(),
+            masked.pattern_score(),
+            note
+        )
+        .unwrap();
+    }
+
+    let format_word = format_info::encode(selected);
+    let unmasked = format_word ^ format_info::FORMAT_MASK;
+
+    writeln!(out).unwrap();
+    writeln!(out, "Format information").unwrap();
+    writeln!(
+        out,
+        "  Data bits            {:03b} {:02b}        symbol number {} ({}), mask {}",
+        format_info::M4_L,
+        selected,
+        format_info::M4_L,
+        format_info::SYMBOL_NAMES[format_info::M4_L as usize],
+        selected
+    )
+    .unwrap();
+    writeln!(out, "  BCH code word        {:015b}", unmasked).unwrap();
+    writeln!(
+        out,
+        "  Format word          {:015b} 0x{:04X} after XOR with 0x{:04X}",
+        format_word,
+        format_word,
+        format_info::FORMAT_MASK
+    )
+    .unwrap();
+
+    out
+}
+
+/// Writes the next `length` bits of the stream as a named field.
+fn field(
+    out: &mut String,
+    bits: &Bits,
+    position: &mut usize,
+    name: &str,
+    length: usize,
+    note: String,
+) {
+    let line = format!(
+        "  {:<20} {:<12} {}",
+        name,
+        bits.string(*position, length),
+        note
+    );
+
+    writeln!(out, "{}", line.trim_end()).unwrap();
+    *position += length;
+}
+
+fn hex(codewords: &[u8]) -> String {
+    codewords
+        .iter()
+        .map(|codeword| format!("{:02X}", codeword))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Most significant bit first view of the data codewords.
+struct Bits<'a>(&'a [u8]);
+
+impl Bits<'_> {
+    fn string(&self, position: usize, length: usize) -> String {
+        (position..position + length)
+            .map(|bit| match (self.0[bit / 8] >> (7 - bit % 8)) & 1 {
+                1 => '1',
+                _ => '0',
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain_stages() {
+        let trace = explain("AC-42", Some(2));
+
+        assert!(trace.contains("Mode indicator       001"));
+        assert!(trace.contains("Character count      00101        5"));
+        assert!(trace.contains("Characters \"AC\"      00111001110  10 x 45 + 12 = 462"));
+        assert!(trace.contains("Character \"2\"        000010       2"));
+        assert!(trace.contains("Terminator           000000000"));
+        assert!(trace.contains("Padding bits         000"));
+        assert!(trace.contains("Pad codewords        EC 11 EC 11"));
+        assert!(trace.contains("  2: right edge"));
+        assert!(trace
+            .lines()
+            .any(|line| line.starts_with("  2:") && line.ends_with("forced")));
+        assert!(trace
+            .lines()
+            .any(|line| line.starts_with("  3:") && line.ends_with("best")));
+        assert!(trace.contains("0x1D5B"));
+    }
+
+    #[test]
+    fn test_explain_truncated_terminator() {
+        let trace = explain("0123456789ABCDEFGHIJK", None);
+
+        assert!(trace.contains("Terminator           0000         truncated"));
+        assert!(!trace.contains("Padding bits"));
+        assert!(trace.contains("Pad codewords        none"));
+    }
+
+    #[test]
+    fn test_explain_selects_best_pattern() {
+        let codewords = data_codewords::generate("HELLO");
+        let best = DataMask::best_pattern(&CodeMatrix::with_data(&codewords)).pattern_ref();
+        let trace = explain("HELLO", None);
+
+        assert!(trace
+            .lines()
+            .any(|line| line.starts_with(&format!("  {}:", best)) && line.ends_with("selected")));
+    }
+}
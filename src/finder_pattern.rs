@@ -0,0 +1,258 @@
+//! Finder patterns in a thresholded image, at any rotation or tilt.
+//!
+//! The 7x7 pattern is a dark ring around a dark 3x3 square, both kept apart from the rest of the
+//! symbol by light modules, so each shows up as its own connected region of dark pixels.
+
+use crate::perspective::Point;
+
+/// Connected regions of dark pixels, touching along edges or at corners.
+pub struct Components {
+    width: u32,
+    height: u32,
+    /// Region of every pixel, row by row, or `None` for a light pixel
+    labels: Vec<Option<usize>>,
+    pub regions: Vec<Region>,
+}
+
+#[derive(Debug, Default)]
+pub struct Region {
+    pub area: usize,
+    /// Mean position of the pixel centres
+    pub centroid: Point,
+    pub pixels: Vec<(u32, u32)>,
+}
+
+impl Components {
+    pub fn label(dark: &[bool], width: u32, height: u32) -> Self {
+        let mut labels = vec![None; dark.len()];
+        let mut regions = Vec::new();
+        let mut stack = Vec::new();
+
+        for start in 0..dark.len() {
+            if !dark[start] || labels[start].is_some() {
+                continue;
+            }
+
+            let label = regions.len();
+            let mut region = Region::default();
+            let (mut sum_x, mut sum_y) = (0.0, 0.0);
+
+            labels[start] = Some(label);
+            stack.push(start);
+
+            while let Some(i) = stack.pop() {
+                let (x, y) = ((i as u32) % width, (i as u32) / width);
+
+                region.pixels.push((x, y));
+                sum_x += x as f64 + 0.5;
+                sum_y += y as f64 + 0.5;
+
+                for (dx, dy) in NEIGHBOURS {
+                    let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+
+                    if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+                        continue;
+                    }
+
+                    let n = (ny * width as i64 + nx) as usize;
+
+                    if dark[n] && labels[n].is_none() {
+                        labels[n] = Some(label);
+                        stack.push(n);
+                    }
+                }
+            }
+
+            region.area = region.pixels.len();
+            region.centroid = (sum_x / region.area as f64, sum_y / region.area as f64);
+            regions.push(region);
+        }
+
+        Self {
+            width,
+            height,
+            labels,
+            regions,
+        }
+    }
+
+    /// Region index of the pixel at an image position, if it is dark.
+    pub fn at(&self, (x, y): Point) -> Option<usize> {
+        if x < 0.0 || y < 0.0 || x >= self.width as f64 || y >= self.height as f64 {
+            return None;
+        }
+
+        self.labels[(y as u32 * self.width + x as u32) as usize]
+    }
+}
+
+const NEIGHBOURS: [(i64, i64); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+// Areas of the ring and of the centre square, in modules
+const RING_AREA: f64 = 24.0;
+const CENTRE_AREA: f64 = 9.0;
+
+/// A finder pattern found in the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Finder {
+    /// Centre of the 3x3 square, at the middle of module (3, 3)
+    pub centre: Point,
+    /// Outer corners of the ring, clockwise as seen in the image, starting from any of them
+    pub corners: [Point; 4],
+    /// Mean module size in pixels, from the area of the ring
+    pub module_size: f64,
+}
+
+/// Every finder pattern in the image, largest first.
+pub fn find(components: &Components) -> Vec<Finder> {
+    let mut finders: Vec<Finder> = components
+        .regions
+        .iter()
+        .enumerate()
+        .filter(|(_, ring)| ring.area as f64 >= RING_AREA)
+        .filter_map(|(label, ring)| {
+            // The centre of a ring lies inside the square it surrounds
+            let centre = &components.regions[components.at(ring.centroid)?];
+            let module_size = (ring.area as f64 / RING_AREA).sqrt();
+            let ratio = centre.area as f64 / ring.area as f64;
+            let offset = distance(ring.centroid, centre.centroid);
+
+            if components.at(ring.centroid) == Some(label)
+                || !(0.4..2.5).contains(&(ratio / (CENTRE_AREA / RING_AREA)))
+                || offset > module_size
+            {
+                return None;
+            }
+
+            Some(Finder {
+                centre: centre.centroid,
+                corners: corners(ring),
+                module_size,
+            })
+        })
+        .collect();
+
+    finders.sort_by(|a, b| b.module_size.total_cmp(&a.module_size));
+    finders
+}
+
+/// Four outermost pixel centres of a roughly square ring: the one furthest from its centroid,
+/// the one furthest from that, and the ones furthest either side of the diagonal between them.
+fn corners(ring: &Region) -> [Point; 4] {
+    let points: Vec<Point> = ring
+        .pixels
+        .iter()
+        .map(|&(x, y)| (x as f64 + 0.5, y as f64 + 0.5))
+        .collect();
+
+    let furthest = |from: Point| {
+        points
+            .iter()
+            .copied()
+            .max_by(|&a, &b| distance(from, a).total_cmp(&distance(from, b)))
+            .unwrap()
+    };
+
+    let first = furthest(ring.centroid);
+    let third = furthest(first);
+    let side = |p: Point| cross(sub(third, first), sub(p, first));
+
+    let second = points
+        .iter()
+        .copied()
+        .max_by(|&a, &b| side(a).total_cmp(&side(b)))
+        .unwrap();
+    let fourth = points
+        .iter()
+        .copied()
+        .min_by(|&a, &b| side(a).total_cmp(&side(b)))
+        .unwrap();
+
+    // Clockwise in image coordinates, with y pointing down, turns with a positive cross product
+    match cross(sub(second, first), sub(third, first)) > 0.0 {
+        true => [first, second, third, fourth],
+        false => [first, fourth, third, second],
+    }
+}
+
+fn sub(a: Point, b: Point) -> Point {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn cross(a: Point, b: Point) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A finder pattern drawn with 4 pixel modules at (8, 8), on a 60x60 image.
+    fn finder_image() -> Vec<bool> {
+        let mut dark = vec![false; 60 * 60];
+
+        for y in 0..28 {
+            for x in 0..28 {
+                let (i, j) = (y / 4, x / 4);
+                let ring = i.min(j).min(6 - i).min(6 - j);
+                dark[((y + 8) * 60 + x + 8) as usize] = ring != 1;
+            }
+        }
+
+        dark
+    }
+
+    #[test]
+    fn test_components() {
+        let dark = [true, false, false, true, false, true, false, true];
+        let components = Components::label(&dark, 4, 2);
+
+        // The first two pixels touch diagonally, the right column is apart
+        assert_eq!(components.regions.len(), 2);
+        assert_eq!(components.at((0.5, 0.5)), components.at((1.5, 1.5)));
+        assert_eq!(components.at((1.5, 0.5)), None);
+    }
+
+    #[test]
+    fn test_find_finder() {
+        let components = Components::label(&finder_image(), 60, 60);
+        let finders = find(&components);
+
+        assert_eq!(finders.len(), 1);
+        assert_eq!(finders[0].centre, (22.0, 22.0));
+        assert!((finders[0].module_size - 4.0).abs() < 1e-9);
+
+        let mut corners = finders[0].corners.to_vec();
+        corners.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            corners,
+            vec![(8.5, 8.5), (8.5, 35.5), (35.5, 8.5), (35.5, 35.5)]
+        );
+    }
+
+    #[test]
+    fn test_solid_square_is_not_a_finder() {
+        let mut dark = vec![false; 40 * 40];
+
+        for y in 5..30 {
+            for x in 5..30 {
+                dark[y * 40 + x] = true;
+            }
+        }
+
+        assert!(find(&Components::label(&dark, 40, 40)).is_empty());
+    }
+}
@@ -1,3 +1,16 @@
+// Format information is a BCH(15,5) code word masked with this pattern
+const BCH_GENERATOR: u16 = 0x537;
+pub const FORMAT_MASK: u16 = 0x4445;
+
+/// Symbol number of M4-L in the format information.
+pub const M4_L: u8 = 5;
+
+/// Micro QR symbol version and error correction level for each symbol number.
+pub const SYMBOL_NAMES: [&str; 8] = ["M1", "M2-L", "M2-M", "M3-L", "M3-M", "M4-L", "M4-M", "M4-Q"];
+
+/// Modules along each side of the symbol for each symbol number.
+pub const SYMBOL_SIZES: [usize; 8] = [11, 13, 13, 15, 15, 17, 17, 17];
+
 fn get_masked_sequence(pattern_ref: u8) -> Option<u16> {
     match pattern_ref {
         0 => Some(0x1735),
@@ -12,6 +25,43 @@ pub fn encode(pattern_ref: u8) -> u16 {
     get_masked_sequence(pattern_ref).unwrap()
 }
 
+/// The masked format word for any symbol number and mask pattern.
+pub fn bch_word(symbol_number: u8, pattern_ref: u8) -> u16 {
+    let data = ((symbol_number as u16) << 2) | pattern_ref as u16;
+    let mut remainder = data << 10;
+
+    for bit in (10..15).rev() {
+        if remainder & (1 << bit) != 0 {
+            remainder ^= BCH_GENERATOR << (bit - 10);
+        }
+    }
+
+    ((data << 10) | remainder) ^ FORMAT_MASK
+}
+
+/// The symbol number and mask pattern of the nearest valid format word, when no more than
+/// 3 bits are wrong.
+pub fn decode(format_info: u16) -> Option<(u8, u8)> {
+    nearest(format_info)
+        .filter(|(distance, _, _)| *distance <= 3)
+        .map(|(_, symbol_number, pattern_ref)| (symbol_number, pattern_ref))
+}
+
+/// Bits that differ between the format information and the nearest valid format word.
+pub fn distance(format_info: u16) -> u32 {
+    nearest(format_info).unwrap().0
+}
+
+fn nearest(format_info: u16) -> Option<(u32, u8, u8)> {
+    (0..8)
+        .flat_map(|symbol_number| (0..4).map(move |pattern_ref| (symbol_number, pattern_ref)))
+        .map(|(symbol_number, pattern_ref)| {
+            let distance = (bch_word(symbol_number, pattern_ref) ^ format_info).count_ones();
+            (distance, symbol_number, pattern_ref)
+        })
+        .min()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -47,4 +97,18 @@ mod tests {
         // This should panic
         encode(4);
     }
+
+    #[test]
+    fn test_bch_word_matches_table() {
+        for pattern_ref in 0..4 {
+            assert_eq!(bch_word(M4_L, pattern_ref), encode(pattern_ref));
+        }
+    }
+
+    #[test]
+    fn test_decode_corrects_bit_errors() {
+        assert_eq!(decode(0x1D5B), Some((M4_L, 2)));
+        assert_eq!(decode(0x1D5B ^ 0b100_0000_0010_0001), Some((M4_L, 2)));
+        assert_eq!(decode(bch_word(1, 3)), Some((1, 3)));
+    }
 }
@@ -3,6 +3,12 @@ pub struct GF256 {
     log: [u8; 256],
 }
 
+impl Default for GF256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GF256 {
     pub fn new() -> Self {
         let mut gf_256 = GF256 {
@@ -43,4 +49,21 @@ impl GF256 {
 
         self.exp[exp_idx]
     }
+
+    pub fn divide(&self, a: usize, b: usize) -> u8 {
+        assert!(b != 0, "division by zero in GF(256)");
+
+        if a == 0 {
+            return 0;
+        }
+
+        let exp_idx = (self.log[a] as usize + 255 - self.log[b] as usize) % 255;
+
+        self.exp[exp_idx]
+    }
+
+    /// The primitive element raised to a power.
+    pub fn power(&self, exponent: usize) -> u8 {
+        self.exp[exponent % 255]
+    }
 }
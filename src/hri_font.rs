@@ -0,0 +1,134 @@
+//! 5x7 bitmap font covering the alphanumeric mode character set, used for human readable interpretation text.
+
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+
+// One column of spacing between glyphs
+const ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+/// Rows of a glyph from top to bottom, the leftmost pixel in bit 4.
+pub fn glyph(c: char) -> [u8; 7] {
+    match c {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '$' => [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '*' => [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        _ => [0x00; 7],
+    }
+}
+
+/// Whole pixel scale that brings the glyph height closest to the requested text height.
+pub fn scale(font_size: u32) -> u32 {
+    ((font_size as f64 / GLYPH_HEIGHT as f64).round() as u32).max(1)
+}
+
+pub fn text_width(text: &str, font_size: u32) -> u32 {
+    let count = text.chars().count() as u32;
+
+    if count == 0 {
+        return 0;
+    }
+
+    (count * ADVANCE - 1) * scale(font_size)
+}
+
+pub fn text_height(font_size: u32) -> u32 {
+    GLYPH_HEIGHT * scale(font_size)
+}
+
+/// Calls `put` with the position of every inked pixel of the text, relative to its top-left corner.
+pub fn draw(text: &str, font_size: u32, mut put: impl FnMut(u32, u32)) {
+    let scale = scale(font_size);
+
+    for (i, c) in text.chars().enumerate() {
+        let origin_x = i as u32 * ADVANCE * scale;
+
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (0x10 >> column) == 0 {
+                    continue;
+                }
+
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        put(origin_x + column * scale + dx, row as u32 * scale + dy);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_alphanumeric_character_has_a_glyph() {
+        for c in "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ$%*+-./:".chars() {
+            assert_ne!(glyph(c), [0x00; 7], "missing glyph for '{}'", c);
+            assert!(glyph(c).iter().all(|row| row & !0x1F == 0));
+        }
+
+        assert_eq!(glyph(' '), [0x00; 7]);
+    }
+
+    #[test]
+    fn test_metrics() {
+        assert_eq!(scale(3), 1);
+        assert_eq!(scale(14), 2);
+        assert_eq!(scale(20), 3);
+        assert_eq!(text_width("AB", 7), 11);
+        assert_eq!(text_width("AB", 14), 22);
+        assert_eq!(text_width("", 14), 0);
+        assert_eq!(text_height(14), 14);
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut pixels = Vec::new();
+        draw("-", 7, |x, y| pixels.push((x, y)));
+
+        assert_eq!(pixels, vec![(0, 3), (1, 3), (2, 3), (3, 3), (4, 3)]);
+    }
+}
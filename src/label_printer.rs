@@ -0,0 +1,141 @@
+use crate::code_matrix::CodeMatrix;
+use crate::symbol_image::{self, Bitmap, ImageOptions};
+use std::fmt::Write;
+
+/// Zebra ZPL II label with the symbol as an ASCII hex `^GF` graphic field.
+pub fn zpl(options: &ImageOptions, matrix: &CodeMatrix) -> Vec<u8> {
+    let bitmap = symbol_image::bitmap(options, matrix);
+    let (x, y) = options.offset;
+    let total_bytes = bitmap.data.len();
+
+    let mut label = String::from("^XA\n");
+
+    write!(
+        label,
+        "^FO{},{}^GFA,{},{},{},",
+        x, y, total_bytes, total_bytes, bitmap.row_bytes
+    )
+    .unwrap();
+
+    for byte in &bitmap.data {
+        write!(label, "{:02X}", byte).unwrap();
+    }
+
+    label.push_str("^FS\n^XZ\n");
+
+    label.into_bytes()
+}
+
+/// Eltron EPL2 label with the symbol as a `GW` direct graphic write.
+pub fn epl(options: &ImageOptions, matrix: &CodeMatrix) -> Vec<u8> {
+    let bitmap = symbol_image::bitmap(options, matrix);
+    let (x, y) = options.offset;
+
+    let mut label =
+        format!("\nN\nGW{},{},{},{},", x, y, bitmap.row_bytes, bitmap.height).into_bytes();
+
+    // EPL prints a cleared bit and leaves a set bit blank
+    label.extend(bitmap.data.iter().map(|byte| !byte));
+    label.extend(b"\nP1\n");
+
+    label
+}
+
+/// ESC/POS receipt printer commands printing the symbol with `GS v 0` raster bit image.
+///
+/// ESC/POS has no absolute positioning, so the offset is applied as a left margin and a paper feed.
+pub fn escpos(options: &ImageOptions, matrix: &CodeMatrix) -> Vec<u8> {
+    let bitmap = symbol_image::bitmap(options, matrix);
+    let (x, y) = options.offset;
+
+    // ESC @ initialise
+    let mut commands = vec![0x1B, 0x40];
+
+    // GS L set left margin
+    commands.extend([0x1D, 0x4C]);
+    commands.extend(le_u16(x));
+
+    // ESC J feeds at most 255 dots at a time
+    let mut feed = y;
+
+    while feed > 0 {
+        let step = feed.min(255);
+        commands.extend([0x1B, 0x4A, step as u8]);
+        feed -= step;
+    }
+
+    commands.extend(raster_bit_image(&bitmap));
+
+    // LF to print the buffer, then reset the left margin
+    commands.push(0x0A);
+    commands.extend([0x1D, 0x4C, 0x00, 0x00]);
+
+    commands
+}
+
+fn raster_bit_image(bitmap: &Bitmap) -> Vec<u8> {
+    // GS v 0 m xL xH yL yH, normal density
+    let mut command = vec![0x1D, 0x76, 0x30, 0x00];
+
+    command.extend(le_u16(bitmap.row_bytes as u32));
+    command.extend(le_u16(bitmap.height));
+    command.extend(&bitmap.data);
+
+    command
+}
+
+fn le_u16(value: u32) -> [u8; 2] {
+    (value.min(u16::MAX as u32) as u16).to_le_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_options() -> ImageOptions {
+        ImageOptions {
+            quiet_zone: 0,
+            offset: (20, 300),
+            ..ImageOptions::new(1)
+        }
+    }
+
+    fn finder_matrix() -> CodeMatrix {
+        let mut matrix = CodeMatrix::new();
+        matrix.write(0, 0, 1);
+        matrix
+    }
+
+    #[test]
+    fn test_zpl_graphic_field() {
+        let zpl = String::from_utf8(zpl(&test_options(), &finder_matrix())).unwrap();
+
+        // 17 dots wide packs into 3 bytes per row, 17 rows
+        assert!(zpl.starts_with("^XA\n^FO20,300^GFA,51,51,3,800000000000"));
+        assert!(zpl.ends_with("^FS\n^XZ\n"));
+    }
+
+    #[test]
+    fn test_epl_graphic_write_inverts_bits() {
+        let epl = epl(&test_options(), &finder_matrix());
+        let header = b"\nN\nGW20,300,3,17,";
+
+        assert!(epl.starts_with(header));
+        assert_eq!(&epl[header.len()..header.len() + 3], &[0x7F, 0xFF, 0xFF]);
+        assert!(epl.ends_with(b"\nP1\n"));
+        assert_eq!(epl.len(), header.len() + 51 + 4);
+    }
+
+    #[test]
+    fn test_escpos_raster_bit_image() {
+        let escpos = escpos(&test_options(), &finder_matrix());
+
+        assert_eq!(&escpos[0..6], &[0x1B, 0x40, 0x1D, 0x4C, 20, 0]);
+
+        // 300 dots of feed takes two ESC J commands
+        assert_eq!(&escpos[6..12], &[0x1B, 0x4A, 255, 0x1B, 0x4A, 45]);
+        assert_eq!(&escpos[12..20], &[0x1D, 0x76, 0x30, 0x00, 3, 0, 17, 0]);
+        assert_eq!(escpos[20], 0x80);
+        assert_eq!(escpos.len(), 20 + 51 + 5);
+    }
+}
@@ -0,0 +1,51 @@
+//! Micro QR Code M4-L generation and reading, shared by the command line tool and the wasm32 build.
+//!
+//! Modules that only serve command line features, such as batches, file output, the HTTP service
+//! and the damage and capture simulations, are left out of the wasm32 build.
+
+pub mod alphanumeric_mode;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod batch;
+pub mod binarise;
+pub mod bit_block;
+pub mod code_matrix;
+pub mod colour;
+pub mod construction;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod damage;
+pub mod data_codewords;
+pub mod data_mask;
+pub mod decoder;
+// Also kept in wasm32 test builds, where the reader tests use it to simulate captures
+#[cfg(any(test, not(target_arch = "wasm32")))]
+pub mod degradation;
+pub mod error_correction;
+pub mod explain;
+pub mod finder_pattern;
+pub mod format_info;
+pub mod gf_256;
+pub mod hri_font;
+pub mod label_printer;
+pub mod matrix_text;
+pub mod output_format;
+pub mod pattern_scoring;
+pub mod perspective;
+pub mod print_gain;
+pub mod print_quality;
+pub mod print_size;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod serial;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod server;
+pub mod symbol_data;
+pub mod symbol_debug;
+pub mod symbol_image;
+pub mod symbol_matrix;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod symbol_output;
+pub mod symbol_pdf;
+pub mod symbol_reader;
+pub mod symbol_sheet;
+pub mod symbol_svg;
+pub mod template;
+pub mod wasm;
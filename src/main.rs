@@ -1,61 +1,1479 @@
-mod alphanumeric_mode;
-mod bit_block;
-mod code_matrix;
-mod data_codewords;
-mod data_mask;
-mod error_correction;
-mod format_info;
-mod gf_256;
-mod pattern_scoring;
-mod symbol_image;
-mod symbol_matrix;
-
-use clap::Parser;
-use regex::Regex;
+use batch::{BatchRow, BatchSettings, InputFormat, Report, RowResult};
+use binarise::{Binariser, Method};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use code_matrix::CodeMatrix;
+use construction::AnimationOptions;
+use damage::{DamageKind, Simulation};
+use data_mask::DataMask;
+use decoder::Decoded;
+use degradation::{Degradation, GroundTruth};
+use image::{Rgba, RgbaImage};
+use output_format::OutputFormat;
+use print_gain::PrintGain;
+use print_quality::Grade;
+use print_size::{PageSize, XDimension};
+use qrc_m4l_gen::{
+    alphanumeric_mode, batch, binarise, code_matrix, colour, construction, damage, data_codewords,
+    data_mask, decoder, degradation, explain, matrix_text, output_format, print_gain,
+    print_quality, print_size, serial, server, symbol_data, symbol_debug, symbol_image,
+    symbol_matrix, symbol_output, symbol_reader, symbol_sheet, template,
+};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serial::{CheckCharacter, Sequence};
+use server::Limits;
+use std::fs::{self, File};
+use std::io::Read;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{process, thread};
+use symbol_data::SymbolData;
+use symbol_debug::DebugOptions;
+use symbol_image::{Hri, ImageOptions};
+use symbol_sheet::SheetLayout;
+
+/// Input file name meaning standard input.
+const STDIN: &str = "-";
+
+// Exit statuses besides 0 for success and 2 for invalid arguments
+const EXIT_FAILURE: i32 = 1;
+const EXIT_UNREADABLE: i32 = 3;
 
 #[derive(Parser, Debug)]
 #[command(
     author = "Ged Dackys <ged@onegood.dev>",
     version = "1.0",
-    about = "Generates an M4-L version micro QR code from an input string encoded in alphanumeric mode. Outputs a 210x210 pixel image file."
+    about = "Generates and reads M4-L version micro QR codes encoded in alphanumeric mode."
 )]
 struct Args {
-    /// Input string (max 21 chars, alphanumeric character set only)
-    #[arg(short, long, value_parser = validate_input)]
-    input: String,
+    #[command(subcommand)]
+    command: Command,
+}
 
-    /// Module size
-    #[arg(short, long, default_value_t = 10)]
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generates a symbol from an input string
+    Encode(EncodeArgs),
+    /// Reads the text from a symbol image or matrix text file
+    Decode(DecodeArgs),
+    /// Shows the version, error correction level, mask, format information and codewords of a symbol
+    Inspect(InspectArgs),
+    /// Reads a generated symbol back and checks it holds the expected input string
+    Verify(VerifyArgs),
+    /// Grades the print quality of a symbol image from A to F
+    Grade(GradeArgs),
+    /// Writes the black and white image the decoder sees after thresholding
+    Binarise(BinariseArgs),
+    /// Draws a symbol with its modules coloured by role, for teaching and debugging
+    Annotate(AnnotateArgs),
+    /// Writes an animated GIF stepping through the construction of a symbol
+    Animate(AnimateArgs),
+    /// Damages a symbol at random many times over and reports how often it still decodes
+    Simulate(SimulateArgs),
+    /// Writes degraded captures of a symbol, as a camera might take them, with their ground truth
+    Degrade(DegradeArgs),
+    /// Generates one symbol per row of a CSV or JSON Lines file
+    Batch(BatchArgs),
+    /// Generates a run of serial numbers from a template such as LOT-{seq:06}
+    Serial(SerialArgs),
+    /// Lays out the symbols of a CSV or JSON Lines file on label sheets
+    Sheet(SheetArgs),
+    /// Serves symbols over HTTP, generated on demand at GET /encode?data=...
+    Serve(ServeArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct EncodeArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Output file name (e.g. my_qrc.png), or - to write the image to standard output
+    #[arg(short, long)]
+    output: String,
+
+    /// Output format, taken from the output file extension when omitted
+    #[arg(short, long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Data mask pattern (0-3) to use instead of the best scoring one
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..4))]
+    mask: Option<u8>,
+
+    /// Print every encoding stage: the bit stream fields, codewords, mask scores and format word
+    #[arg(long)]
+    explain: bool,
+
+    /// Mirror the symbol about its diagonal, for marking on the back of clear parts to be read
+    /// through them
+    #[arg(long)]
+    mirror: bool,
+
+    #[command(flatten)]
+    render: RenderArgs,
+}
+
+#[derive(clap::Args, Debug)]
+#[group(required = true, multiple = false)]
+struct InputArgs {
+    /// Input string (max 21 chars, alphanumeric character set only), or - to read it from standard input
+    #[arg(short, long)]
+    input: Option<String>,
+
+    /// Read the input string from a file. A single trailing line ending is ignored
+    #[arg(long)]
+    input_file: Option<String>,
+}
+
+impl InputArgs {
+    /// The validated input string, exiting if it cannot be read or is not valid.
+    fn read(&self) -> String {
+        let bytes = match (&self.input, &self.input_file) {
+            (Some(input), _) if input == STDIN => {
+                let mut bytes = Vec::new();
+                std::io::stdin()
+                    .read_to_end(&mut bytes)
+                    .map(|_| bytes)
+                    .map_err(|err| format!("Cannot read standard input: {}", err))
+            }
+            (Some(input), _) => Ok(input.clone().into_bytes()),
+            (None, Some(file)) => {
+                fs::read(file).map_err(|err| format!("Cannot read {}: {}", file, err))
+            }
+            (None, None) => unreachable!(),
+        };
+
+        bytes
+            .and_then(|bytes| alphanumeric_mode::validate_bytes(&bytes))
+            .unwrap_or_else(|err| {
+                Args::command()
+                    .error(ErrorKind::ValueValidation, err)
+                    .exit()
+            })
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct DecodeArgs {
+    /// Raster image of a symbol, a .txt matrix of 1 and 0 modules, or - for standard input
+    file: String,
+
+    /// Decode every symbol in a raster image, of any Micro QR version, printing the position,
+    /// orientation and version of each
+    #[arg(long)]
+    all: bool,
+
+    /// Print the symbols found with --all as JSON
+    #[arg(long, requires = "all")]
+    json: bool,
+}
+
+#[derive(clap::Args, Debug)]
+#[group(required = true, multiple = false)]
+struct InspectArgs {
+    /// Raster image of an upright symbol, a .txt matrix of 1 and 0 modules, or - for standard input
+    file: Option<String>,
+
+    /// Inspect the symbol that would be generated for this input string instead
+    #[arg(short, long, value_parser = alphanumeric_mode::validate_input)]
+    input: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct VerifyArgs {
+    /// Raster image of an upright symbol, a .txt matrix of 1 and 0 modules, or - for standard input
+    file: String,
+
+    #[command(flatten)]
+    input: InputArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct GradeArgs {
+    /// Raster image of an upright symbol, or - for standard input
+    file: String,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// Exit with status 1 when the overall grade is below this one
+    #[arg(long, value_enum, ignore_case = true)]
+    min_grade: Option<MinGrade>,
+}
+
+#[derive(clap::Args, Debug)]
+struct BinariseArgs {
+    /// Raster image of a symbol, or - for standard input
+    file: String,
+
+    /// Output PNG file name, or - to write to standard output
+    #[arg(short, long)]
+    output: String,
+
+    /// Thresholding method
+    #[arg(long, value_enum, default_value_t = Method::Sauvola)]
+    method: Method,
+
+    /// Side of the neighbourhood of each pixel for local methods, in pixels (default: a quarter of
+    /// the smaller image side)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(3..))]
+    window: Option<u32>,
+
+    /// Sauvola's k (default: 0.2), Niblack's k (default: -0.2) or the block mean offset in
+    /// reflectance (default: 0.05)
+    #[arg(short, long, allow_hyphen_values = true)]
+    k: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+#[value(rename_all = "UPPER")]
+enum MinGrade {
+    A,
+    B,
+    C,
+    D,
+}
+
+impl From<MinGrade> for Grade {
+    fn from(grade: MinGrade) -> Self {
+        match grade {
+            MinGrade::A => Grade::A,
+            MinGrade::B => Grade::B,
+            MinGrade::C => Grade::C,
+            MinGrade::D => Grade::D,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct AnnotateArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Output file name (SVG or a raster format), or - to write to standard output
+    #[arg(short, long)]
+    output: String,
+
+    /// Output format, taken from the output file extension when omitted
+    #[arg(short, long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Data mask pattern (0-3) to use instead of the best scoring one
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..4))]
+    mask: Option<u8>,
+
+    /// Module size in pixels
+    #[arg(short, long, default_value_t = 32, value_parser = clap::value_parser!(u32).range(4..))]
     module_size: u32,
 
-    /// Output file name (e.g. my_qrc.png)
+    /// Quiet zone width in modules
+    #[arg(short, long, default_value_t = symbol_image::QUIET_ZONE)]
+    quiet_zone: u32,
+
+    /// Label codeword modules with their codeword (D0-D15, E0-E7) and bit (7 most significant),
+    /// and format information modules with their bit
+    #[arg(long)]
+    labels: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct AnimateArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Output GIF file name, or - to write to standard output
     #[arg(short, long)]
     output: String,
+
+    /// Data mask pattern (0-3) to use instead of the best scoring one
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..4))]
+    mask: Option<u8>,
+
+    /// Module size in pixels
+    #[arg(short, long, default_value_t = 12, value_parser = clap::value_parser!(u32).range(1..))]
+    module_size: u32,
+
+    /// Quiet zone width in modules
+    #[arg(short, long, default_value_t = symbol_image::QUIET_ZONE)]
+    quiet_zone: u32,
+
+    /// Time each frame is shown in milliseconds. The finished symbol is shown four times as long
+    #[arg(long, default_value_t = 600, value_parser = clap::value_parser!(u32).range(1..))]
+    frame_duration: u32,
+}
+
+#[derive(clap::Args, Debug)]
+struct SimulateArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Kinds of damage to apply, as a comma separated list
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [DamageKind::Flips, DamageKind::Scratch, DamageKind::Blot, DamageKind::Tear])]
+    damage: Vec<DamageKind>,
+
+    /// Severities to try instead of the defaults for each kind: modules flipped, scratch length,
+    /// blot radius or tear size in modules
+    #[arg(long, value_delimiter = ',')]
+    severity: Vec<u32>,
+
+    /// Data mask pattern (0-3) to test, otherwise every pattern is tested
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..4))]
+    mask: Option<u8>,
+
+    /// Trials for every combination of mask, damage and severity
+    #[arg(long, default_value_t = 200, value_parser = clap::value_parser!(u32).range(1..))]
+    trials: u32,
+
+    /// Random number seed, so that runs can be repeated
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Print the results as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct DegradeArgs {
+    #[command(flatten)]
+    input: InputArgs,
+
+    /// Directory the images and ground_truth.jsonl are written to
+    #[arg(long, default_value = ".")]
+    output_dir: PathBuf,
+
+    /// Number of images to write
+    #[arg(short, long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    count: u32,
+
+    /// Largest Gaussian blur standard deviation in pixels
+    #[arg(long, default_value_t = 0.0)]
+    blur: f64,
+
+    /// Largest Gaussian noise standard deviation in grey levels
+    #[arg(long, default_value_t = 0.0)]
+    noise: f64,
+
+    /// Largest perspective skew, as the corner displacement over the image side, from 0 to 0.5 (e.g. 0.1)
+    #[arg(long, default_value_t = 0.0)]
+    skew: f64,
+
+    /// Largest rotation in degrees, either way
+    #[arg(long, default_value_t = 0.0)]
+    rotation: f64,
+
+    /// Largest brightness lost across the image, from 0 to 1
+    #[arg(long, default_value_t = 0.0)]
+    illumination: f64,
+
+    /// Lowest JPEG quality (1-100) to compress with, otherwise images are not compressed
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    jpeg_quality: Option<u8>,
+
+    /// Apply the given values to every image instead of drawing each one at random up to them
+    #[arg(long)]
+    exact: bool,
+
+    /// Data mask pattern (0-3) to use instead of the best scoring one
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..4))]
+    mask: Option<u8>,
+
+    /// Module size in pixels
+    #[arg(short, long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+    module_size: u32,
+
+    /// Quiet zone width in modules
+    #[arg(short, long, default_value_t = symbol_image::QUIET_ZONE)]
+    quiet_zone: u32,
+
+    /// Random number seed, so that runs can be repeated
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+#[derive(clap::Args, Debug)]
+struct BatchArgs {
+    /// CSV file with a header row, or JSON Lines file with one object per line. Each row needs a
+    /// data field and may set version, ec, mask (0-3) and filename
+    file: String,
+
+    /// Input file format, taken from the file extension when omitted
+    #[arg(long, value_enum)]
+    input_format: Option<InputFormat>,
+
+    /// Directory the images are written to
+    #[arg(long, default_value = ".")]
+    output_dir: PathBuf,
+
+    /// Output file name template with {row}, {row:06} (zero padded) and {data} placeholders
+    #[arg(long, default_value = "{row}.png")]
+    filename_template: String,
+
+    /// Output format for every row, otherwise taken from each file name
+    #[arg(short, long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Number of worker threads (default: one per CPU)
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    jobs: Option<u32>,
+
+    /// Write a JSON report of every row to this file
+    #[arg(long)]
+    report: Option<String>,
+
+    #[command(flatten)]
+    render: RenderArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct SerialArgs {
+    /// Code template with a {seq} or zero padded {seq:06} placeholder for the sequence number
+    template: String,
+
+    /// First sequence number
+    #[arg(long, default_value_t = 1)]
+    start: u64,
+
+    /// Number of codes to generate
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..))]
+    count: u64,
+
+    /// Increment between sequence numbers
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    step: u64,
+
+    /// Append a check character to every code
+    #[arg(long, value_enum)]
+    check: Option<CheckCharacter>,
+
+    /// Directory the images are written to
+    #[arg(long, default_value = ".", conflicts_with = "sheet")]
+    output_dir: PathBuf,
+
+    /// Output file name template with {seq}, {seq:06} (zero padded) and {data} placeholders
+    #[arg(long, default_value = "{data}.png", conflicts_with = "sheet")]
+    filename_template: String,
+
+    /// Write every code to a single raster image instead, or - for standard output
+    #[arg(long)]
+    sheet: Option<String>,
+
+    /// Symbols per row of the sheet
+    #[arg(long, default_value_t = 5, requires = "sheet", value_parser = clap::value_parser!(u32).range(1..))]
+    columns: u32,
+
+    /// Gap between symbols on the sheet in pixels
+    #[arg(long, default_value_t = 0, requires = "sheet")]
+    gap: u32,
+
+    /// Output format, otherwise taken from the file names
+    #[arg(short, long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Number of worker threads (default: one per CPU)
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    jobs: Option<u32>,
+
+    #[command(flatten)]
+    render: RenderArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct SheetArgs {
+    /// CSV or JSON Lines file in the same format as the batch subcommand
+    file: String,
+
+    /// Input file format, taken from the file extension when omitted
+    #[arg(long, value_enum)]
+    input_format: Option<InputFormat>,
+
+    /// Output file name (PDF, SVG or a raster format). Pages after the first are written to
+    /// numbered files, except for PDF which holds every page
+    #[arg(short, long)]
+    output: String,
+
+    /// Output format, taken from the output file extension when omitted
+    #[arg(short, long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Page size: a4, a5, a6, letter, legal or WIDTHxHEIGHT in mm or in (e.g. 100x150mm)
+    #[arg(long, value_parser = print_size::parse_page_size, default_value = "a4")]
+    page_size: PageSize,
+
+    /// Page margin in mm
+    #[arg(long, default_value_t = 10.0)]
+    margin: f64,
+
+    /// Gap between label columns in mm
+    #[arg(long, default_value_t = 0.0)]
+    gutter: f64,
+
+    /// Gap between label rows in mm (default: the column gutter)
+    #[arg(long)]
+    row_gutter: Option<f64>,
+
+    /// Labels across the page (default: as many as fit)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    columns: Option<u32>,
+
+    /// Labels down the page (default: as many as fit)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    rows: Option<u32>,
+
+    /// Draw crop marks in the margin in line with the label edges
+    #[arg(long)]
+    crop_marks: bool,
+
+    #[command(flatten)]
+    render: RenderArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct ServeArgs {
+    /// Address and port to listen on. Only local tools can connect to the default
+    #[arg(short, long, default_value = "127.0.0.1:8080")]
+    bind: String,
+
+    /// Largest module size a request may ask for, in pixels
+    #[arg(long, default_value_t = 40, value_parser = clap::value_parser!(u32).range(1..))]
+    max_module_size: u32,
+
+    /// Requests handled at once, beyond which new connections get a 503 response
+    #[arg(long, default_value_t = 16, value_parser = clap::value_parser!(u32).range(1..))]
+    max_connections: u32,
+
+    /// Seconds allowed for receiving each request and sending its response
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    timeout: u64,
+}
+
+#[derive(clap::Args, Debug)]
+struct RenderArgs {
+    /// Module size
+    #[arg(short, long, default_value_t = 10, conflicts_with = "x_dimension")]
+    module_size: u32,
+
+    /// Physical module size (X-dimension) in mm or mils (e.g. 0.5mm, 20mil), requires --dpi
+    #[arg(short, long, value_parser = print_size::parse_x_dimension, requires = "dpi")]
+    x_dimension: Option<XDimension>,
+
+    /// Printer resolution in dots per inch, written to PNG, TIFF and BMP metadata
+    #[arg(short, long, value_parser = clap::value_parser!(u32).range(1..))]
+    dpi: Option<u32>,
+
+    /// Quiet zone width in modules (the specification requires at least 2)
+    #[arg(short, long, default_value_t = symbol_image::QUIET_ZONE)]
+    quiet_zone: u32,
+
+    /// Dark module colour as #RRGGBB, #RRGGBBAA, black, white or transparent
+    #[arg(long, value_parser = colour::parse_colour, default_value = "black")]
+    foreground: Rgba<u8>,
+
+    /// Light module and quiet zone colour as #RRGGBB, #RRGGBBAA, black, white or transparent
+    #[arg(long, value_parser = colour::parse_colour, default_value = "white")]
+    background: Rgba<u8>,
+
+    /// Reflectance reversal: light modules on a dark background (e.g. laser-marked metal)
+    #[arg(long)]
+    invert: bool,
+
+    /// Print gain compensation taken off every dark region, in pixels or micrometres (e.g. 1px, 40um)
+    #[arg(short = 'g', long, value_parser = print_gain::parse_print_gain)]
+    print_gain: Option<PrintGain>,
+
+    /// Print the input string as human readable text under the symbol
+    #[arg(long)]
+    hri: bool,
+
+    /// Human readable text height in pixels (default: twice the module size)
+    #[arg(long, requires = "hri")]
+    hri_font_size: Option<u32>,
+
+    /// Gap above and below the human readable text in pixels (default: the module size)
+    #[arg(long, requires = "hri")]
+    hri_spacing: Option<u32>,
+
+    /// Horizontal position of the symbol on the label in printer dots (ZPL, EPL and ESC/POS output)
+    #[arg(long, default_value_t = 0)]
+    offset_x: u32,
+
+    /// Vertical position of the symbol on the label in printer dots (ZPL, EPL and ESC/POS output)
+    #[arg(long, default_value_t = 0)]
+    offset_y: u32,
+}
+
+impl RenderArgs {
+    /// Image options for the arguments, exiting on invalid combinations and warning about ones
+    /// that may not scan.
+    fn image_options(&self, hri_text: &str) -> ImageOptions {
+        let module_size = match (self.x_dimension, self.dpi) {
+            (Some(x_dimension), Some(dpi)) => {
+                let achieved = x_dimension.achieved(dpi);
+
+                eprintln!(
+                    "Module size: {} dots at {} dpi, X-dimension {:.3} mm ({:.1} mil)",
+                    x_dimension.module_dots(dpi),
+                    dpi,
+                    achieved.mm(),
+                    achieved.mils()
+                );
+
+                x_dimension.module_dots(dpi)
+            }
+            _ => self.module_size,
+        };
+
+        let mut image_options = ImageOptions {
+            quiet_zone: self.quiet_zone,
+            foreground: self.foreground,
+            background: self.background,
+            dpi: self.dpi,
+            offset: (self.offset_x, self.offset_y),
+            hri: self.hri.then(|| Hri {
+                text: hri_text.to_string(),
+                font_size: self.hri_font_size.unwrap_or(module_size * 2),
+                spacing: self.hri_spacing.unwrap_or(module_size),
+            }),
+            ..ImageOptions::new(module_size)
+        };
+
+        if let Some(print_gain) = self.print_gain {
+            image_options.print_gain = print_gain.to_pixels(self.dpi).unwrap_or_else(|err| {
+                Args::command()
+                    .error(ErrorKind::MissingRequiredArgument, err)
+                    .exit()
+            });
+
+            if image_options.print_gain >= module_size as f64 {
+                Args::command()
+                    .error(
+                        ErrorKind::ValueValidation,
+                        "Print gain must be smaller than the module size",
+                    )
+                    .exit();
+            }
+        }
+
+        if self.invert {
+            image_options = image_options.with_reversed_reflectance();
+        }
+
+        if image_options.quiet_zone < symbol_image::QUIET_ZONE {
+            eprintln!(
+                "Warning: a quiet zone narrower than {} modules may prevent the symbol from being read",
+                symbol_image::QUIET_ZONE
+            );
+        }
+
+        if image_options.symbol_contrast() < colour::MIN_SYMBOL_CONTRAST {
+            eprintln!(
+                "Warning: symbol contrast {:.0}% is below the {:.0}% needed for reliable scanning",
+                image_options.symbol_contrast() * 100.0,
+                colour::MIN_SYMBOL_CONTRAST * 100.0
+            );
+        }
+
+        image_options
+    }
 }
 
 fn main() {
-    let args = Args::parse();
+    match Args::parse().command {
+        Command::Encode(encode_args) => run_encode(encode_args),
+        Command::Decode(decode_args) => run_decode(decode_args),
+        Command::Inspect(inspect_args) => run_inspect(inspect_args),
+        Command::Verify(verify_args) => run_verify(verify_args),
+        Command::Grade(grade_args) => run_grade(grade_args),
+        Command::Binarise(binarise_args) => run_binarise(binarise_args),
+        Command::Annotate(annotate_args) => run_annotate(annotate_args),
+        Command::Animate(animate_args) => run_animate(animate_args),
+        Command::Simulate(simulate_args) => run_simulate(simulate_args),
+        Command::Degrade(degrade_args) => run_degrade(degrade_args),
+        Command::Batch(batch_args) => run_batch(batch_args),
+        Command::Serial(serial_args) => run_serial(serial_args),
+        Command::Sheet(sheet_args) => run_sheet(sheet_args),
+        Command::Serve(serve_args) => run_serve(serve_args),
+    }
+}
+
+fn run_encode(args: EncodeArgs) {
+    let format = args
+        .format
+        .or_else(|| OutputFormat::from_file_name(&args.output))
+        .unwrap_or_else(|| {
+            Args::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "Cannot tell the output format from the file name, use --format",
+                )
+                .exit()
+        });
+
+    let input = args.input.read();
+    let image_options = args.render.image_options(&input);
+
+    if args.explain {
+        let trace = explain::explain(&input, args.mask);
+
+        // Keep the trace out of a symbol written to standard output
+        if args.output == symbol_output::STDOUT {
+            eprint!("{}", trace);
+        } else {
+            print!("{}", trace);
+        }
+    }
+
+    let data_codewords = data_codewords::generate(&input);
+    let mut symbol_matrix = symbol_matrix::generate(&data_codewords, forced_mask(args.mask));
+
+    if args.mirror {
+        symbol_matrix = symbol_matrix.transposed();
+    }
+
+    if let Some(mask) = args.mask {
+        let data_matrix = CodeMatrix::with_data(&data_codewords);
+        let scores = DataMask::scores(&data_matrix);
+        let best = DataMask::best_pattern(&data_matrix).pattern_ref();
+
+        eprintln!(
+            "Mask {} forced: score {}, the best scoring mask {} scores {}",
+            mask, scores[mask as usize], best, scores[best as usize]
+        );
+    }
+
+    symbol_output::write(&args.output, format, &image_options, &symbol_matrix).unwrap();
+}
+
+fn run_decode(args: DecodeArgs) {
+    if !args.all {
+        let decoded = read_symbol(&args.file);
+
+        if decoded.mirrored {
+            eprintln!("Mirror image detected");
+        }
+
+        println!("{}", decoded.text);
+        return;
+    }
+
+    let found = symbol_reader::read_all(&read_image(&args.file))
+        .and_then(|found| match found.is_empty() {
+            true => Err(String::from("No symbol found")),
+            false => Ok(found),
+        })
+        .unwrap_or_else(|err| {
+            eprintln!("Cannot read {}: {}", args.file, err);
+            process::exit(EXIT_UNREADABLE);
+        });
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&found).unwrap());
+        return;
+    }
+
+    for symbol in found {
+        let (x, y) = symbol.centre;
+        let payload = match (symbol.text, symbol.error) {
+            (Some(text), _) => text,
+            (None, error) => format!("error: {}", error.unwrap_or_default()),
+        };
+
+        println!(
+            "({:.1}, {:.1})  {:>5.1}°  {:<4}  {}{}",
+            x,
+            y,
+            symbol.orientation,
+            symbol.version.unwrap_or("?"),
+            match symbol.mirrored {
+                true => "mirrored  ",
+                false => "",
+            },
+            payload
+        );
+    }
+}
+
+fn run_inspect(args: InspectArgs) {
+    let decoded = match (args.file, args.input) {
+        (Some(file), _) => read_symbol(&file),
+        (None, Some(input)) => {
+            let matrix = symbol_matrix::generate(&data_codewords::generate(&input), None);
+            decoder::decode(&matrix).unwrap()
+        }
+        (None, None) => unreachable!(),
+    };
+
+    let hex = |codewords: &[u8]| {
+        codewords
+            .iter()
+            .map(|codeword| format!("{:02X}", codeword))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let (data, ec) = decoded.codewords.split_at(decoder::DATA_CODEWORDS);
+
+    println!("Text:             {}", decoded.text);
+    println!("Version:          M4");
+    println!("Error correction: L");
+    println!("Mask pattern:     {}", decoded.pattern_ref);
+    println!(
+        "Mirror image:     {}",
+        match decoded.mirrored {
+            true => "yes",
+            false => "no",
+        }
+    );
+    println!(
+        "Format word:      0x{:04X} ({:015b})",
+        decoded.format_info, decoded.format_info
+    );
+    println!("Data codewords:   {}", hex(data));
+    println!("EC codewords:     {}", hex(ec));
+    println!("Errors corrected: {}", decoded.errors_corrected);
+    println!(
+        "Capacity used:    {} of {} data bits ({:.0}%), {} of {} alphanumeric characters",
+        decoded.data_bits,
+        decoder::DATA_BITS,
+        decoded.data_bits as f64 / decoder::DATA_BITS as f64 * 100.0,
+        decoded.text.chars().count(),
+        alphanumeric_mode::MAX_INPUT_LENGTH
+    );
+}
+
+fn run_verify(args: VerifyArgs) {
+    if args.file == STDIN && args.input.input.as_deref() == Some(STDIN) {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "The symbol and the input string cannot both be read from standard input",
+            )
+            .exit();
+    }
+
+    let input = args.input.read();
+    let decoded = read_symbol(&args.file);
+
+    if decoded.text != input {
+        eprintln!(
+            "Mismatch: {} holds \"{}\", expected \"{}\"",
+            args.file, decoded.text, input
+        );
+        process::exit(EXIT_FAILURE);
+    }
+
+    println!("OK: {} holds \"{}\"", args.file, decoded.text);
+
+    if decoded.errors_corrected > 0 {
+        eprintln!(
+            "Warning: {} codewords needed error correction",
+            decoded.errors_corrected
+        );
+    }
+}
+
+/// Reads and decodes a symbol file, exiting with the unreadable status on failure.
+fn read_symbol(file: &str) -> Decoded {
+    let decoded = if file == STDIN {
+        let mut bytes = Vec::new();
+
+        std::io::stdin()
+            .read_to_end(&mut bytes)
+            .map_err(|err| err.to_string())
+            .and_then(|_| decode_bytes(&bytes))
+    } else if OutputFormat::from_file_name(file) == Some(OutputFormat::Text) {
+        fs::read_to_string(file)
+            .map_err(|err| err.to_string())
+            .and_then(|text| matrix_text::parse(&text))
+            .and_then(|matrix| decoder::decode(&matrix))
+    } else {
+        image::open(file)
+            .map_err(|err| err.to_string())
+            .and_then(|img| symbol_reader::decode_image(&img.to_rgba8()))
+    };
+
+    decoded.unwrap_or_else(|err| {
+        eprintln!("Cannot read {}: {}", file, err);
+        process::exit(EXIT_UNREADABLE);
+    })
+}
+
+/// Decodes a symbol from the bytes of a text matrix or an image file.
+fn decode_bytes(bytes: &[u8]) -> Result<Decoded, String> {
+    if let Some(matrix) = std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|text| matrix_text::parse(text).ok())
+    {
+        return decoder::decode(&matrix);
+    }
+
+    image::load_from_memory(bytes)
+        .map_err(|err| err.to_string())
+        .and_then(|img| symbol_reader::decode_image(&img.to_rgba8()))
+}
+
+/// Reads a raster image from a file or standard input, exiting with status 3 if it cannot be read.
+fn read_image(file: &str) -> RgbaImage {
+    let bytes = match file {
+        STDIN => {
+            let mut bytes = Vec::new();
+            std::io::stdin().read_to_end(&mut bytes).map(|_| bytes)
+        }
+        file => fs::read(file),
+    };
+
+    bytes
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| image::load_from_memory(&bytes).map_err(|err| err.to_string()))
+        .map(|img| img.to_rgba8())
+        .unwrap_or_else(|err| {
+            eprintln!("Cannot read {}: {}", file, err);
+            process::exit(EXIT_UNREADABLE);
+        })
+}
+
+fn run_grade(args: GradeArgs) {
+    let report = print_quality::grade(&read_image(&args.file));
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        print!("{}", report.text_report());
+    }
+
+    if let Some(min_grade) = args.min_grade {
+        if report.overall < Grade::from(min_grade) {
+            process::exit(EXIT_FAILURE);
+        }
+    }
+}
+
+fn run_binarise(args: BinariseArgs) {
+    let img = read_image(&args.file);
+    let (width, height) = img.dimensions();
+    let defaults = Binariser::new(args.method, width, height);
+
+    let binariser = Binariser {
+        window: args.window.map_or(defaults.window, |window| window | 1),
+        k: args.k.unwrap_or(defaults.k),
+        ..defaults
+    };
+
+    let reflectance = symbol_reader::reflectance_map(&img);
+    let dark = binariser
+        .dark_pixels(&reflectance, width, height)
+        .unwrap_or_else(|err| {
+            eprintln!("Cannot threshold {}: {}", args.file, err);
+            process::exit(EXIT_UNREADABLE);
+        });
+
+    let mut bytes = Vec::new();
+    binarise::image(&dark, width, height)
+        .write_to(
+            &mut std::io::Cursor::new(&mut bytes),
+            image::ImageFormat::Png,
+        )
+        .unwrap();
+
+    symbol_output::write_bytes(&args.output, &bytes).unwrap();
+}
+
+fn run_annotate(args: AnnotateArgs) {
+    let format = args
+        .format
+        .or_else(|| OutputFormat::from_file_name(&args.output))
+        .unwrap_or_else(|| {
+            Args::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "Cannot tell the output format from the file name, use --format",
+                )
+                .exit()
+        });
+
+    if format != OutputFormat::Svg && !format.is_raster() {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                "Annotated symbols can only be written as SVG or a raster format",
+            )
+            .exit();
+    }
+
+    let input = args.input.read();
+    let matrix = symbol_matrix::generate(&data_codewords::generate(&input), forced_mask(args.mask));
+
+    let options = DebugOptions {
+        module_size: args.module_size,
+        quiet_zone: args.quiet_zone,
+        labels: args.labels,
+    };
+
+    let bytes = match format {
+        OutputFormat::Svg => symbol_debug::svg(&options, &matrix).into_bytes(),
+        _ => symbol_image::encode_image(format, &symbol_debug::raster(&options, &matrix), None)
+            .unwrap(),
+    };
+
+    symbol_output::write_bytes(&args.output, &bytes).unwrap();
+}
+
+fn run_animate(args: AnimateArgs) {
+    let input = args.input.read();
+    let steps = construction::steps(&data_codewords::generate(&input), args.mask);
+
+    let options = AnimationOptions {
+        module_size: args.module_size,
+        quiet_zone: args.quiet_zone,
+        frame_duration_ms: args.frame_duration,
+    };
+
+    let bytes = construction::gif(&options, &steps).unwrap();
+
+    symbol_output::write_bytes(&args.output, &bytes).unwrap();
+}
+
+fn run_simulate(args: SimulateArgs) {
+    let input = args.input.read();
+    let codewords = data_codewords::generate(&input);
+
+    let symbols: Vec<(u8, CodeMatrix)> = match args.mask {
+        Some(mask) => vec![mask],
+        None => (0..4).collect(),
+    }
+    .into_iter()
+    .map(|mask| {
+        (
+            mask,
+            symbol_matrix::generate(&codewords, DataMask::from_pattern_ref(mask)),
+        )
+    })
+    .collect();
 
-    let input_data = args.input;
-    let module_size = args.module_size;
-    let output_filename = args.output;
+    let simulation = Simulation {
+        text: &input,
+        damage: &args.damage,
+        severities: &args.severity,
+        trials: args.trials,
+        seed: args.seed,
+    };
 
-    let data_codewords = data_codewords::generate(&input_data);
-    let symbol_matrix = symbol_matrix::generate(&data_codewords);
+    let results = damage::simulate(&simulation, &symbols);
 
-    symbol_image::write(&output_filename, module_size, &symbol_matrix);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&results).unwrap());
+    } else {
+        print!("{}", damage::table(&results));
+    }
 }
 
-fn validate_input(s: &str) -> Result<String, String> {
-    if s.len() > 21 {
-        return Err(String::from("Input must not exceed 21 characters"));
+fn run_degrade(args: DegradeArgs) {
+    if !(0.0..=1.0).contains(&args.illumination) {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                "The illumination fall-off must be between 0 and 1",
+            )
+            .exit();
+    }
+
+    if !(0.0..=0.5).contains(&args.skew) {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                "The skew must be between 0 and 0.5",
+            )
+            .exit();
+    }
+
+    let input = args.input.read();
+    let matrix = symbol_matrix::generate(&data_codewords::generate(&input), forced_mask(args.mask));
+    let options = ImageOptions {
+        quiet_zone: args.quiet_zone,
+        ..ImageOptions::new(args.module_size)
+    };
+    let img = symbol_image::render(&options, &matrix);
+
+    let near = (args.quiet_zone * args.module_size) as f64;
+    let far = near + (17 * args.module_size) as f64;
+    let symbol = [(near, near), (far, near), (far, far), (near, far)];
+
+    let limits = Degradation {
+        blur: args.blur,
+        noise: args.noise,
+        skew: args.skew,
+        rotation: args.rotation,
+        illumination: args.illumination,
+        jpeg_quality: args.jpeg_quality,
+    };
+
+    fs::create_dir_all(&args.output_dir).unwrap();
+
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    let mut ground_truth = String::new();
+
+    for n in 1..=args.count {
+        let degradation = match args.exact {
+            true => limits,
+            false => limits.sample(&mut rng),
+        };
+
+        let capture = match degradation::capture(&img, symbol, &degradation, &mut rng) {
+            Ok(capture) => capture,
+            Err(err) => {
+                eprintln!("Cannot degrade image {}: {}", n, err);
+                process::exit(EXIT_FAILURE);
+            }
+        };
+        let file = format!("{:04}.png", n);
+        capture.image.save(args.output_dir.join(&file)).unwrap();
+
+        let truth = GroundTruth {
+            file,
+            symbol: SymbolData::new(&matrix),
+            module_size: args.module_size,
+            quiet_zone: args.quiet_zone,
+            degradation,
+            illumination_angle: capture.illumination_angle,
+            corners: capture.corners,
+        };
+
+        ground_truth.push_str(&serde_json::to_string(&truth).unwrap());
+        ground_truth.push('\n');
     }
 
-    let re = Regex::new(r"^[0-9A-Z $%*+\-./:]*$").unwrap();
+    fs::write(args.output_dir.join("ground_truth.jsonl"), ground_truth).unwrap();
+}
+
+fn run_batch(args: BatchArgs) {
+    let input_format = args
+        .input_format
+        .or_else(|| InputFormat::from_file_name(&args.file))
+        .unwrap_or_else(|| {
+            Args::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "Cannot tell the input format from the file name, use --input-format",
+                )
+                .exit()
+        });
+
+    let file = File::open(&args.file).unwrap_or_else(|err| {
+        Args::command()
+            .error(ErrorKind::Io, format!("Cannot open {}: {}", args.file, err))
+            .exit()
+    });
+
+    fs::create_dir_all(&args.output_dir).unwrap();
+
+    let settings = BatchSettings {
+        options: args.render.image_options(""),
+        filename_template: args.filename_template,
+        output_dir: args.output_dir,
+        format: args.format,
+    };
+
+    let rows = batch::read_rows(file, input_format);
+    let results = batch::run(&rows, &settings, worker_count(args.jobs));
 
-    if !re.is_match(s) {
-        return Err(String::from("Input contains invalid characters. Only alphanumeric (0-9, A-Z) and special characters ( $%*+-./:) are allowed"));
+    finish_batch(&results, args.report.as_deref());
+}
+
+fn run_serial(args: SerialArgs) {
+    let sequence = Sequence {
+        template: args.template,
+        start: args.start,
+        count: args.count,
+        step: args.step,
+        check: args.check,
+    };
+
+    let codes = sequence.codes().unwrap_or_else(|err| {
+        Args::command()
+            .error(ErrorKind::ValueValidation, err)
+            .exit()
+    });
+
+    let options = args.render.image_options("");
+
+    if let Some(sheet) = args.sheet {
+        let format = args
+            .format
+            .or_else(|| OutputFormat::from_file_name(&sheet))
+            .unwrap_or_else(|| {
+                Args::command()
+                    .error(
+                        ErrorKind::MissingRequiredArgument,
+                        "Cannot tell the sheet format from the file name, use --format",
+                    )
+                    .exit()
+            });
+
+        if !format.is_raster() {
+            Args::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    "Sheets can only be written in raster formats",
+                )
+                .exit();
+        }
+
+        let symbols: Vec<_> = codes
+            .into_iter()
+            .map(|(_, code)| {
+                let matrix = symbol_matrix::generate(&data_codewords::generate(&code), None);
+                (code, matrix)
+            })
+            .collect();
+
+        let img = symbol_sheet::render(&options, &symbols, args.columns, args.gap);
+        let bytes = symbol_image::encode_image(format, &img, options.dpi).unwrap();
+
+        symbol_output::write_bytes(&sheet, &bytes).unwrap();
+        return;
     }
 
-    Ok(s.to_string())
+    let rows: Vec<_> = codes
+        .into_iter()
+        .enumerate()
+        .map(|(i, (seq, code))| {
+            let filename = template::expand(
+                &args.filename_template,
+                &[("seq", seq)],
+                &[("data", &batch::safe_file_name(&code))],
+            );
+
+            let row = filename.map(|filename| BatchRow {
+                data: code,
+                version: None,
+                ec: None,
+                mask: None,
+                filename: Some(filename),
+            });
+
+            (i + 1, row)
+        })
+        .collect();
+
+    fs::create_dir_all(&args.output_dir).unwrap();
+
+    let settings = BatchSettings {
+        options,
+        filename_template: args.filename_template,
+        output_dir: args.output_dir,
+        format: args.format,
+    };
+
+    let results = batch::run(&rows, &settings, worker_count(args.jobs));
+
+    finish_batch(&results, None);
+}
+
+fn run_sheet(args: SheetArgs) {
+    let input_format = args
+        .input_format
+        .or_else(|| InputFormat::from_file_name(&args.file))
+        .unwrap_or_else(|| {
+            Args::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "Cannot tell the input format from the file name, use --input-format",
+                )
+                .exit()
+        });
+
+    let format = args
+        .format
+        .or_else(|| OutputFormat::from_file_name(&args.output))
+        .unwrap_or_else(|| {
+            Args::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "Cannot tell the output format from the file name, use --format",
+                )
+                .exit()
+        });
+
+    if matches!(
+        format,
+        OutputFormat::Zpl
+            | OutputFormat::Epl
+            | OutputFormat::Escpos
+            | OutputFormat::Text
+            | OutputFormat::Json
+            | OutputFormat::Csv
+    ) {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                "Sheets can only be written as PDF, SVG or a raster format",
+            )
+            .exit();
+    }
+
+    let file = File::open(&args.file).unwrap_or_else(|err| {
+        Args::command()
+            .error(ErrorKind::Io, format!("Cannot open {}: {}", args.file, err))
+            .exit()
+    });
+
+    let mut failed = 0;
+    let mut symbols = Vec::new();
+
+    for (row, parsed) in batch::read_rows(file, input_format) {
+        match parsed.and_then(|batch_row| Ok((batch_row.data.clone(), batch::symbol(&batch_row)?)))
+        {
+            Ok(symbol) => symbols.push(symbol),
+            Err(err) => {
+                eprintln!("Row {}: {}", row, err);
+                failed += 1;
+            }
+        }
+    }
+
+    if symbols.is_empty() {
+        eprintln!("No symbols to lay out");
+        process::exit(EXIT_FAILURE);
+    }
+
+    let mut options = args.render.image_options("");
+    let dpi = *options.dpi.get_or_insert(symbol_sheet::DEFAULT_DPI);
+
+    // Labels are sized for the widest human readable text
+    let (symbol_width, symbol_height) =
+        symbols.iter().fold((0, 0), |(width, height), (text, _)| {
+            let layout = options.clone().with_hri_text(text).layout();
+            (width.max(layout.width), height.max(layout.height))
+        });
+    let symbol_width = print_size::dots_to_mm(symbol_width as f64, dpi);
+    let symbol_height = print_size::dots_to_mm(symbol_height as f64, dpi);
+
+    let gutter = (args.gutter, args.row_gutter.unwrap_or(args.gutter));
+
+    let sheet = SheetLayout {
+        page: args.page_size,
+        margin: args.margin,
+        gutter,
+        columns: args.columns.unwrap_or_else(|| {
+            SheetLayout::fit(args.page_size.width, args.margin, gutter.0, symbol_width)
+        }),
+        rows: args.rows.unwrap_or_else(|| {
+            SheetLayout::fit(args.page_size.height, args.margin, gutter.1, symbol_height)
+        }),
+        crop_marks: args.crop_marks,
+    };
+
+    sheet
+        .check_fit(symbol_width, symbol_height)
+        .unwrap_or_else(|err| {
+            Args::command()
+                .error(ErrorKind::ValueValidation, err)
+                .exit()
+        });
+
+    let pages = match format {
+        OutputFormat::Pdf => vec![symbol_sheet::pdf(&sheet, &options, &symbols)],
+        OutputFormat::Svg => symbol_sheet::svg_pages(&sheet, &options, &symbols)
+            .into_iter()
+            .map(String::into_bytes)
+            .collect(),
+        _ => symbol_sheet::raster_pages(&sheet, &options, &symbols)
+            .iter()
+            .map(|img| symbol_image::encode_image(format, img, Some(dpi)).unwrap())
+            .collect(),
+    };
+
+    if pages.len() > 1 && args.output == symbol_output::STDOUT {
+        Args::command()
+            .error(
+                ErrorKind::ValueValidation,
+                "Sheets with more than one page cannot be written to standard output, except as PDF",
+            )
+            .exit();
+    }
+
+    for (i, page) in pages.iter().enumerate() {
+        let destination = match pages.len() {
+            1 => args.output.clone(),
+            _ => symbol_sheet::page_file_name(&args.output, i + 1),
+        };
+
+        symbol_output::write_bytes(&destination, page).unwrap();
+    }
+
+    eprintln!(
+        "{} labels on {} pages of {} x {}, {} failed",
+        symbols.len(),
+        symbols.len().div_ceil(sheet.labels_per_page()),
+        sheet.columns,
+        sheet.rows,
+        failed
+    );
+
+    if failed > 0 {
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+fn run_serve(args: ServeArgs) {
+    let limits = Limits {
+        max_module_size: args.max_module_size,
+        max_connections: args.max_connections as usize,
+        timeout: Duration::from_secs(args.timeout),
+        ..Limits::default()
+    };
+
+    let listener = TcpListener::bind(&args.bind).unwrap_or_else(|err| {
+        Args::command()
+            .error(
+                ErrorKind::Io,
+                format!("Cannot listen on {}: {}", args.bind, err),
+            )
+            .exit()
+    });
+
+    eprintln!("Listening on http://{}", listener.local_addr().unwrap());
+
+    if let Err(err) = server::serve(listener, limits) {
+        eprintln!("Server stopped: {}", err);
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+/// The data mask for a `--mask` argument, which clap has already limited to 0-3.
+fn forced_mask(mask: Option<u8>) -> Option<DataMask> {
+    mask.and_then(DataMask::from_pattern_ref)
+}
+
+fn worker_count(jobs: Option<u32>) -> usize {
+    jobs.map(|jobs| jobs as usize).unwrap_or_else(|| {
+        thread::available_parallelism()
+            .map(|jobs| jobs.get())
+            .unwrap_or(1)
+    })
+}
+
+/// Prints the failed rows and a summary, writes the JSON report and exits with status 1 if any row failed.
+fn finish_batch(results: &[RowResult], report_file: Option<&str>) {
+    let report = Report::new(results);
+
+    for result in results {
+        if let Some(error) = &result.error {
+            eprintln!("Row {}: {}", result.row, error);
+        }
+    }
+
+    println!(
+        "{} of {} symbols generated, {} failed",
+        report.succeeded, report.total, report.failed
+    );
+
+    if let Some(report_file) = report_file {
+        fs::write(report_file, serde_json::to_string_pretty(&report).unwrap()).unwrap();
+    }
+
+    if report.failed > 0 {
+        process::exit(EXIT_FAILURE);
+    }
 }
@@ -0,0 +1,87 @@
+//! Plain text symbol matrix: one line per row, `1` or `#` for a dark module and `0` or `.` for a light one.
+
+use crate::code_matrix::CodeMatrix;
+
+const SIZE: usize = 17;
+
+pub fn render(matrix: &CodeMatrix) -> String {
+    let mut text = String::with_capacity(SIZE * (SIZE + 1));
+
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            text.push(if matrix.read(y, x) == 1 { '1' } else { '0' });
+        }
+
+        text.push('\n');
+    }
+
+    text
+}
+
+/// Parses a matrix, ignoring blank lines and whitespace within rows.
+pub fn parse(text: &str) -> Result<CodeMatrix, String> {
+    let rows: Vec<Vec<char>> = text
+        .lines()
+        .map(|line| {
+            line.chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<Vec<_>>()
+        })
+        .filter(|row| !row.is_empty())
+        .collect();
+
+    if rows.len() != SIZE || rows.iter().any(|row| row.len() != SIZE) {
+        return Err(format!("Matrix must be {} rows of {} modules", SIZE, SIZE));
+    }
+
+    let mut matrix = CodeMatrix::new();
+
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.iter().enumerate() {
+            let value = match c {
+                '1' | '#' => 1,
+                '0' | '.' => 0,
+                _ => return Err(format!("Invalid module '{}' in row {}", c, y + 1)),
+            };
+
+            matrix.write(y, x, value);
+        }
+    }
+
+    Ok(matrix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data_codewords, symbol_matrix};
+
+    #[test]
+    fn test_render_and_parse_round_trip() {
+        let matrix = symbol_matrix::generate(&data_codewords::generate("TEXT"), None);
+        let text = render(&matrix);
+
+        assert!(text.starts_with("11111110101010101\n10000010"));
+
+        let parsed = parse(&text).unwrap();
+        assert_eq!(render(&parsed), text);
+    }
+
+    #[test]
+    fn test_parse_alternative_characters() {
+        let text = render(&CodeMatrix::new())
+            .replace('0', ". ")
+            .replacen(". ", "#", 1);
+
+        let parsed = parse(&format!("\n{}\n", text)).unwrap();
+
+        assert_eq!(parsed.read(0, 0), 1);
+        assert_eq!(parsed.read(0, 1), 0);
+    }
+
+    #[test]
+    fn test_parse_rejects_wrong_size() {
+        assert!(parse("0101\n").is_err());
+        assert!(parse(&render(&CodeMatrix::new()).replace('0', "x")).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use clap::ValueEnum;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// 8-bit RGBA PNG
+    Png,
+    /// 1-bit grayscale PNG for label printers
+    #[value(name = "png1")]
+    MonoPng,
+    /// Portable bitmap (1-bit)
+    Pbm,
+    /// Portable graymap (8-bit)
+    Pgm,
+    Bmp,
+    Tiff,
+    Gif,
+    Webp,
+    Jpeg,
+    Svg,
+    Pdf,
+    /// Zebra ZPL II label
+    Zpl,
+    /// Eltron EPL2 label
+    Epl,
+    /// ESC/POS receipt printer commands
+    Escpos,
+    /// Plain text module matrix
+    #[value(name = "txt")]
+    Text,
+    /// Module matrix and encoding metadata
+    Json,
+    /// Encoding metadata and module rows as field, value pairs
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        let extension = Path::new(file_name)
+            .extension()
+            .and_then(|ext| ext.to_str())?
+            .to_ascii_lowercase();
+
+        match extension.as_str() {
+            "png" => Some(OutputFormat::Png),
+            "pbm" => Some(OutputFormat::Pbm),
+            "pgm" => Some(OutputFormat::Pgm),
+            "bmp" => Some(OutputFormat::Bmp),
+            "tif" | "tiff" => Some(OutputFormat::Tiff),
+            "gif" => Some(OutputFormat::Gif),
+            "webp" => Some(OutputFormat::Webp),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+            "svg" => Some(OutputFormat::Svg),
+            "pdf" => Some(OutputFormat::Pdf),
+            "zpl" => Some(OutputFormat::Zpl),
+            "epl" => Some(OutputFormat::Epl),
+            "escpos" | "pos" => Some(OutputFormat::Escpos),
+            "txt" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            "csv" => Some(OutputFormat::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn is_raster(self) -> bool {
+        !matches!(
+            self,
+            OutputFormat::Svg
+                | OutputFormat::Pdf
+                | OutputFormat::Zpl
+                | OutputFormat::Epl
+                | OutputFormat::Escpos
+                | OutputFormat::Text
+                | OutputFormat::Json
+                | OutputFormat::Csv
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_file_name() {
+        assert_eq!(
+            OutputFormat::from_file_name("a.PNG"),
+            Some(OutputFormat::Png)
+        );
+        assert_eq!(
+            OutputFormat::from_file_name("a.tif"),
+            Some(OutputFormat::Tiff)
+        );
+        assert_eq!(
+            OutputFormat::from_file_name("dir.v2/a.jpeg"),
+            Some(OutputFormat::Jpeg)
+        );
+        assert_eq!(OutputFormat::from_file_name("a.xyz"), None);
+        assert_eq!(OutputFormat::from_file_name("-"), None);
+    }
+}
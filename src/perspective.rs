@@ -0,0 +1,250 @@
+//! Plane projective transforms, mapping one quadrilateral onto another or fitted to many point pairs.
+
+pub type Point = (f64, f64);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Homography([f64; 9]);
+
+impl Homography {
+    /// The transform taking each of the four `from` points to the matching `to` point, or `None`
+    /// when three of the points are in a line.
+    pub fn from_quad(from: [Point; 4], to: [Point; 4]) -> Option<Self> {
+        let pairs: Vec<(Point, Point)> = from.into_iter().zip(to).collect();
+
+        Self::fit(&pairs)
+    }
+
+    /// The least squares transform taking each point pair's first point to its second, from four
+    /// or more pairs, or `None` when the points do not fix a transform.
+    ///
+    /// Both sets of points are first moved to the origin and scaled to a mean distance of 1 from
+    /// it, so that pixel coordinates in the hundreds do not swamp the solution.
+    pub fn fit(pairs: &[(Point, Point)]) -> Option<Self> {
+        if pairs.len() < 4 {
+            return None;
+        }
+
+        let from = Self::normalising(pairs.iter().map(|pair| pair.0));
+        let to = Self::normalising(pairs.iter().map(|pair| pair.1));
+
+        // Normal equations of two equations per pair in the first eight matrix entries, the
+        // last one being 1
+        let mut system = [[0.0; 9]; 8];
+
+        for &(a, b) in pairs {
+            let ((x, y), (u, v)) = (from.map(a), to.map(b));
+
+            for row in [
+                [x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, u],
+                [0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y, v],
+            ] {
+                for (equation, &scale) in system.iter_mut().zip(&row) {
+                    for (value, &term) in equation.iter_mut().zip(&row) {
+                        *value += scale * term;
+                    }
+                }
+            }
+        }
+
+        let solution = solve(system)?;
+        let mut h = [1.0; 9];
+        h[..8].copy_from_slice(&solution);
+
+        // Points that do not fix a transform leave a least squares fit collapsing the plane
+        if Self(h).determinant().abs() < 1e-9 {
+            return None;
+        }
+
+        Some(from.then(&Self(h)).then(&to.inverse()?))
+    }
+
+    /// Similarity moving the centroid of the points to the origin, at a mean distance of 1.
+    fn normalising(points: impl Iterator<Item = Point> + Clone) -> Self {
+        let count = points.clone().count() as f64;
+        let (sum_x, sum_y) = points
+            .clone()
+            .fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+        let (cx, cy) = (sum_x / count, sum_y / count);
+        let spread = points.map(|(x, y)| (x - cx).hypot(y - cy)).sum::<f64>() / count;
+        let scale = match spread > 0.0 {
+            true => 1.0 / spread,
+            false => 1.0,
+        };
+
+        Self([
+            scale,
+            0.0,
+            -scale * cx,
+            0.0,
+            scale,
+            -scale * cy,
+            0.0,
+            0.0,
+            1.0,
+        ])
+    }
+
+    /// This transform followed by the next one.
+    pub fn then(&self, next: &Self) -> Self {
+        let (a, b) = (&next.0, &self.0);
+        let mut product = [0.0; 9];
+
+        for (i, value) in product.iter_mut().enumerate() {
+            let (row, column) = (i / 3, i % 3);
+            *value = (0..3).map(|k| a[row * 3 + k] * b[k * 3 + column]).sum();
+        }
+
+        Self(product)
+    }
+
+    pub fn map(&self, (x, y): Point) -> Point {
+        let h = &self.0;
+        let w = h[6] * x + h[7] * y + h[8];
+
+        (
+            (h[0] * x + h[1] * y + h[2]) / w,
+            (h[3] * x + h[4] * y + h[5]) / w,
+        )
+    }
+
+    fn determinant(&self) -> f64 {
+        let [a, b, c, d, e, f, g, h, i] = self.0;
+        a * (e * i - f * h) - b * (d * i - f * g) + c * (d * h - e * g)
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        let [a, b, c, d, e, f, g, h, i] = self.0;
+        let determinant = self.determinant();
+
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+
+        let adjugate = [
+            e * i - f * h,
+            c * h - b * i,
+            b * f - c * e,
+            f * g - d * i,
+            a * i - c * g,
+            c * d - a * f,
+            d * h - e * g,
+            b * g - a * h,
+            a * e - b * d,
+        ];
+
+        Some(Self(adjugate.map(|value| value / determinant)))
+    }
+}
+
+/// Value of a row by row image between pixel centres, at a position measured from the image's top
+/// left corner, or `None` outside the image.
+pub fn interpolate(values: &[f64], width: u32, height: u32, (x, y): Point) -> Option<f64> {
+    if x < 0.0 || y < 0.0 || x > width as f64 || y > height as f64 {
+        return None;
+    }
+
+    let (x, y) = (
+        (x - 0.5).clamp(0.0, width as f64 - 1.0),
+        (y - 0.5).clamp(0.0, height as f64 - 1.0),
+    );
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+    let at = |x: u32, y: u32| values[(y * width + x) as usize];
+
+    Some(
+        (at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx) * (1.0 - fy)
+            + (at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx) * fy,
+    )
+}
+
+/// Gaussian elimination with partial pivoting on an augmented 8x8 system.
+fn solve(mut system: [[f64; 9]; 8]) -> Option<[f64; 8]> {
+    for column in 0..8 {
+        let pivot = (column..8)
+            .max_by(|&a, &b| system[a][column].abs().total_cmp(&system[b][column].abs()))?;
+
+        if system[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+
+        system.swap(column, pivot);
+
+        for row in 0..8 {
+            if row != column {
+                let pivot_row = system[column];
+                let factor = system[row][column] / pivot_row[column];
+
+                for (value, pivot_value) in system[row].iter_mut().zip(pivot_row).skip(column) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+
+    let mut solution = [0.0; 8];
+
+    for (i, value) in solution.iter_mut().enumerate() {
+        *value = system[i][8] / system[i][i];
+    }
+
+    Some(solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Point, b: Point) {
+        assert!(
+            (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn test_quad_to_quad() {
+        let from = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let to = [(3.0, 1.0), (14.0, 2.5), (12.0, 13.0), (1.0, 11.0)];
+        let homography = Homography::from_quad(from, to).unwrap();
+
+        for (&point, &expected) in from.iter().zip(to.iter()) {
+            assert_close(homography.map(point), expected);
+        }
+
+        let inverse = homography.inverse().unwrap();
+        assert_close(inverse.map(homography.map((4.0, 7.0))), (4.0, 7.0));
+    }
+
+    #[test]
+    fn test_fit_many_points() {
+        let exact = Homography::from_quad(
+            [(0.0, 0.0), (17.0, 0.0), (17.0, 17.0), (0.0, 17.0)],
+            [(120.0, 80.0), (410.0, 130.0), (380.0, 450.0), (90.0, 400.0)],
+        )
+        .unwrap();
+
+        let pairs: Vec<(Point, Point)> = (0..18)
+            .flat_map(|i| [(i as f64, 0.5), (0.5, i as f64)])
+            .chain([(3.5, 3.5)])
+            .map(|point| (point, exact.map(point)))
+            .collect();
+        let fitted = Homography::fit(&pairs).unwrap();
+
+        assert!(
+            (fitted.map((16.5, 16.5)).0 - exact.map((16.5, 16.5)).0).abs() < 1e-6
+                && (fitted.map((16.5, 16.5)).1 - exact.map((16.5, 16.5)).1).abs() < 1e-6
+        );
+        assert!(Homography::fit(&pairs[..3]).is_none());
+    }
+
+    #[test]
+    fn test_degenerate_quad() {
+        let from = [(0.0, 0.0), (1.0, 1.0), (2.0, 2.0), (0.0, 5.0)];
+        let to = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+
+        assert!(Homography::from_quad(from, to).is_none());
+    }
+}
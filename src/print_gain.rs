@@ -0,0 +1,266 @@
+use crate::code_matrix::CodeMatrix;
+
+const MODULE_COUNT: usize = 17;
+const MICROMETRES_PER_INCH: f64 = 25400.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrintGain {
+    Pixels(f64),
+    Micrometres(f64),
+}
+
+impl PrintGain {
+    /// Total width removed from each dark region, in output pixels (printer dots).
+    pub fn to_pixels(self, dpi: Option<u32>) -> Result<f64, String> {
+        match (self, dpi) {
+            (PrintGain::Pixels(px), _) => Ok(px),
+            (PrintGain::Micrometres(um), Some(dpi)) => Ok(um / MICROMETRES_PER_INCH * dpi as f64),
+            (PrintGain::Micrometres(_), None) => Err(String::from(
+                "Print gain in micrometres requires the printer resolution (--dpi)",
+            )),
+        }
+    }
+}
+
+/// Parses a print gain such as `2px` or `40um`.
+pub fn parse_print_gain(s: &str) -> Result<PrintGain, String> {
+    let s = s.trim();
+
+    let (value, unit): (&str, fn(f64) -> PrintGain) = if let Some(value) = s.strip_suffix("px") {
+        (value, PrintGain::Pixels)
+    } else if let Some(value) = s.strip_suffix("um") {
+        (value, PrintGain::Micrometres)
+    } else if let Some(value) = s.strip_suffix("µm") {
+        (value, PrintGain::Micrometres)
+    } else {
+        return Err(String::from(
+            "Print gain must end with a unit: px or um (e.g. 2px, 40um)",
+        ));
+    };
+
+    let value: f64 = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid print gain value: {}", s))?;
+
+    if !(value.is_finite() && value >= 0.0) {
+        return Err(String::from("Print gain must not be negative"));
+    }
+
+    Ok(unit(value))
+}
+
+/// The part of a dark module left after bar width reduction, in module-local coordinates.
+///
+/// Only edges that face a light module (or the quiet zone) are pulled in, so runs of dark modules stay joined.
+/// Where both orthogonal neighbours are dark but the diagonal one is light, the corner is notched instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModuleOutline {
+    /// Distance each reduced edge is pulled in, half of the total reduction
+    pub inset: f64,
+    /// Reduced edges: left, top, right, bottom
+    pub edges: [bool; 4],
+    /// Corner notches: top-left, top-right, bottom-right, bottom-left
+    pub notches: [bool; 4],
+}
+
+impl ModuleOutline {
+    /// Whether a point inside a module of the given size is still inked.
+    pub fn contains(&self, x: f64, y: f64, module_size: f64) -> bool {
+        let (x0, y0, x1, y1) = self.bounds(module_size);
+
+        if x < x0 || x >= x1 || y < y0 || y >= y1 {
+            return false;
+        }
+
+        let in_left = x < self.inset;
+        let in_top = y < self.inset;
+        let in_right = x >= module_size - self.inset;
+        let in_bottom = y >= module_size - self.inset;
+
+        !((self.notches[0] && in_left && in_top)
+            || (self.notches[1] && in_right && in_top)
+            || (self.notches[2] && in_right && in_bottom)
+            || (self.notches[3] && in_left && in_bottom))
+    }
+
+    /// Outline as a closed polygon, clockwise from the top-left corner.
+    pub fn polygon(&self, module_size: f64) -> Vec<(f64, f64)> {
+        let (x0, y0, x1, y1) = self.bounds(module_size);
+        let (nl, nt) = (self.inset, self.inset);
+        let (nr, nb) = (module_size - self.inset, module_size - self.inset);
+
+        let mut points = Vec::with_capacity(12);
+
+        if self.notches[0] {
+            points.extend([(x0, nt), (nl, nt), (nl, y0)]);
+        } else {
+            points.push((x0, y0));
+        }
+
+        if self.notches[1] {
+            points.extend([(nr, y0), (nr, nt), (x1, nt)]);
+        } else {
+            points.push((x1, y0));
+        }
+
+        if self.notches[2] {
+            points.extend([(x1, nb), (nr, nb), (nr, y1)]);
+        } else {
+            points.push((x1, y1));
+        }
+
+        if self.notches[3] {
+            points.extend([(nl, y1), (nl, nb), (x0, nb)]);
+        } else {
+            points.push((x0, y1));
+        }
+
+        points
+    }
+
+    fn bounds(&self, module_size: f64) -> (f64, f64, f64, f64) {
+        let inset = |reduced: bool| if reduced { self.inset } else { 0.0 };
+
+        (
+            inset(self.edges[0]),
+            inset(self.edges[1]),
+            module_size - inset(self.edges[2]),
+            module_size - inset(self.edges[3]),
+        )
+    }
+}
+
+/// Computes the reduced outline of the dark module at (y, x), splitting the total reduction across both edges.
+pub fn module_outline(matrix: &CodeMatrix, y: usize, x: usize, reduction: f64) -> ModuleOutline {
+    let is_light = |dy: isize, dx: isize| {
+        let (ny, nx) = (y as isize + dy, x as isize + dx);
+
+        if ny < 0 || nx < 0 || ny >= MODULE_COUNT as isize || nx >= MODULE_COUNT as isize {
+            return true;
+        }
+
+        matrix.read(ny as usize, nx as usize) == 0
+    };
+
+    let notch = |dy: isize, dx: isize| {
+        reduction > 0.0 && !is_light(dy, 0) && !is_light(0, dx) && is_light(dy, dx)
+    };
+
+    ModuleOutline {
+        inset: reduction / 2.0,
+        edges: [
+            is_light(0, -1),
+            is_light(-1, 0),
+            is_light(0, 1),
+            is_light(1, 0),
+        ],
+        notches: [notch(-1, -1), notch(-1, 1), notch(1, 1), notch(1, -1)],
+    }
+}
+
+/// Reduced outlines of every dark module, keyed by (y, x) module position.
+pub fn dark_module_outlines(
+    matrix: &CodeMatrix,
+    reduction: f64,
+) -> Vec<(usize, usize, ModuleOutline)> {
+    let mut outlines = Vec::new();
+
+    for y in 0..MODULE_COUNT {
+        for x in 0..MODULE_COUNT {
+            if matrix.read(y, x) == 1 {
+                outlines.push((y, x, module_outline(matrix, y, x, reduction)));
+            }
+        }
+    }
+
+    outlines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_print_gain() {
+        assert_eq!(parse_print_gain("2px").unwrap(), PrintGain::Pixels(2.0));
+        assert_eq!(
+            parse_print_gain("40um").unwrap(),
+            PrintGain::Micrometres(40.0)
+        );
+        assert_eq!(
+            parse_print_gain("12.5 µm").unwrap(),
+            PrintGain::Micrometres(12.5)
+        );
+        assert!(parse_print_gain("2").is_err());
+        assert!(parse_print_gain("-1px").is_err());
+    }
+
+    #[test]
+    fn test_print_gain_to_pixels() {
+        assert_eq!(PrintGain::Pixels(3.0).to_pixels(None).unwrap(), 3.0);
+        assert!((PrintGain::Micrometres(254.0).to_pixels(Some(300)).unwrap() - 3.0).abs() < 1e-9);
+        assert!(PrintGain::Micrometres(40.0).to_pixels(None).is_err());
+    }
+
+    #[test]
+    fn test_isolated_module_shrinks_on_all_sides() {
+        let mut matrix = CodeMatrix::new();
+        matrix.write(5, 5, 1);
+
+        let outline = module_outline(&matrix, 5, 5, 3.0);
+
+        assert_eq!(outline.edges, [true; 4]);
+        assert_eq!(outline.notches, [false; 4]);
+        assert_eq!(
+            outline.polygon(10.0),
+            vec![(1.5, 1.5), (8.5, 1.5), (8.5, 8.5), (1.5, 8.5)]
+        );
+
+        // Sampled at pixel centres, one pixel goes from the left and two from the right
+        assert!(!outline.contains(0.5, 5.0, 10.0));
+        assert!(outline.contains(1.5, 5.0, 10.0));
+        assert!(outline.contains(7.5, 5.0, 10.0));
+        assert!(!outline.contains(8.5, 5.0, 10.0));
+    }
+
+    #[test]
+    fn test_adjacent_modules_stay_merged() {
+        let mut matrix = CodeMatrix::new();
+        matrix.write(5, 5, 1);
+        matrix.write(5, 6, 1);
+
+        let left = module_outline(&matrix, 5, 5, 2.0);
+        let right = module_outline(&matrix, 5, 6, 2.0);
+
+        assert!(!left.edges[2]);
+        assert!(!right.edges[0]);
+        assert!(left.contains(9.9, 5.0, 10.0));
+        assert!(right.contains(0.0, 5.0, 10.0));
+    }
+
+    #[test]
+    fn test_inner_corner_is_notched() {
+        let mut matrix = CodeMatrix::new();
+        matrix.write(5, 5, 1);
+        matrix.write(5, 6, 1);
+        matrix.write(6, 5, 1);
+
+        let outline = module_outline(&matrix, 5, 5, 2.0);
+
+        assert_eq!(outline.notches, [false, false, true, false]);
+        assert!(!outline.contains(9.5, 9.5, 10.0));
+        assert!(outline.contains(9.5, 8.5, 10.0));
+        assert_eq!(outline.polygon(10.0).len(), 6);
+    }
+
+    #[test]
+    fn test_edge_of_symbol_borders_quiet_zone() {
+        let mut matrix = CodeMatrix::new();
+        matrix.write(0, 0, 1);
+
+        let outline = module_outline(&matrix, 0, 0, 2.0);
+
+        assert_eq!(outline.edges, [true; 4]);
+    }
+}
@@ -0,0 +1,504 @@
+//! Print quality grading of a symbol image, after the method of ISO/IEC 15415 with the Micro QR
+//! specific parameters of ISO/IEC 18004 and the DPM guidance of ISO/IEC 29158.
+//!
+//! Reflectance is measured over a square aperture of half the module pitch at each module centre,
+//! as a stand-in for the synthetic circular aperture of the standard.
+
+use crate::code_matrix::{self, CodeMatrix, ModuleRole};
+use crate::data_mask::DataMask;
+use crate::decoder::{self, Decoded};
+use crate::format_info;
+use crate::symbol_reader::{self, Grid};
+use image::RgbaImage;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+const MODULE_COUNT: usize = 17;
+const CODEWORDS: usize = 24;
+
+// Aperture side as a fraction of the module pitch
+const APERTURE: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Grade {
+    F,
+    D,
+    C,
+    B,
+    A,
+}
+
+impl Grade {
+    /// Grade of a value against the lower bounds of grades A to D.
+    fn at_least(value: f64, bounds: [f64; 4]) -> Self {
+        [Grade::A, Grade::B, Grade::C, Grade::D]
+            .into_iter()
+            .zip(bounds)
+            .find(|&(_, bound)| value >= bound)
+            .map_or(Grade::F, |(grade, _)| grade)
+    }
+
+    /// Grade of a value against the upper bounds of grades A to D.
+    fn at_most(value: f64, bounds: [f64; 4]) -> Self {
+        Self::at_least(-value, bounds.map(|bound| -bound))
+    }
+
+    /// Grade of a count of damaged modules or bits, one grade lost for each.
+    fn from_errors(errors: usize) -> Self {
+        match errors {
+            0 => Grade::A,
+            1 => Grade::B,
+            2 => Grade::C,
+            3 => Grade::D,
+            _ => Grade::F,
+        }
+    }
+}
+
+impl fmt::Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Serialize for Grade {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Parameter {
+    pub value: f64,
+    pub grade: Grade,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QualityReport {
+    pub overall: Grade,
+    pub text: Option<String>,
+    pub decode_error: Option<String>,
+    pub decode: Parameter,
+    /// Highest minus lowest reflectance
+    pub symbol_contrast: Parameter,
+    /// Lowest codeword modulation, graded allowing for error correction
+    pub modulation: Option<Parameter>,
+    /// Lowest codeword reflectance margin, graded allowing for error correction
+    pub reflectance_margin: Option<Parameter>,
+    /// Finder, separator and timing modules on the wrong side of the threshold
+    pub fixed_pattern_damage: Option<Parameter>,
+    pub axial_nonuniformity: Option<Parameter>,
+    /// Largest timing module centre deviation from the grid, in modules
+    pub grid_nonuniformity: Option<Parameter>,
+    pub unused_error_correction: Option<Parameter>,
+    /// Format information bits differing from the nearest valid word
+    pub format_info_damage: Option<Parameter>,
+}
+
+/// Grades a symbol image. Parameters that need a decoded symbol are left out when decoding fails.
+pub fn grade(img: &RgbaImage) -> QualityReport {
+    let reflectance = symbol_reader::reflectance_map(img);
+    let (min, max) = symbol_reader::reflectance_range(&reflectance);
+    let contrast = max - min;
+
+    let symbol_contrast = Parameter {
+        value: contrast,
+        grade: Grade::at_least(contrast, [0.70, 0.55, 0.40, 0.20]),
+    };
+
+    let (decoded, grid) = match symbol_reader::read(img) {
+        Ok(read) => read,
+        Err(err) => {
+            return QualityReport {
+                overall: Grade::F,
+                text: None,
+                decode_error: Some(err),
+                decode: Parameter {
+                    value: 0.0,
+                    grade: Grade::F,
+                },
+                symbol_contrast,
+                modulation: None,
+                reflectance_margin: None,
+                fixed_pattern_damage: None,
+                axial_nonuniformity: None,
+                grid_nonuniformity: None,
+                unused_error_correction: None,
+                format_info_damage: None,
+            }
+        }
+    };
+
+    let threshold = (min + max) / 2.0;
+    let ideal = ideal_matrix(&decoded);
+
+    // Modulation, and margin signed by whether the module reads as the colour it should be
+    let mut modulation = [[0.0; MODULE_COUNT]; MODULE_COUNT];
+    let mut margin = [[0.0; MODULE_COUNT]; MODULE_COUNT];
+
+    for y in 0..MODULE_COUNT {
+        for x in 0..MODULE_COUNT {
+            // A module with no pixels in the image has no modulation, failing its codeword
+            let Some(r) = aperture_reflectance(img, &reflectance, &grid, y, x) else {
+                continue;
+            };
+            let reads_dark = (r < threshold) != grid.reversed;
+
+            modulation[y][x] = 2.0 * (r - threshold).abs() / contrast;
+            margin[y][x] = match reads_dark == (ideal.read(y, x) == 1) {
+                true => modulation[y][x],
+                false => 0.0,
+            };
+        }
+    }
+
+    let modulation_bounds = [0.50, 0.40, 0.30, 0.20];
+    let modulation = codeword_parameter(&modulation, modulation_bounds);
+    let reflectance_margin = codeword_parameter(&margin, modulation_bounds);
+
+    let fixed_pattern_errors = (0..MODULE_COUNT)
+        .flat_map(|y| (0..MODULE_COUNT).map(move |x| (y, x)))
+        .filter(|&(y, x)| {
+            !matches!(
+                code_matrix::module_role(y, x),
+                ModuleRole::Codeword { .. } | ModuleRole::FormatInfo(_)
+            )
+        })
+        .filter(|&(y, x)| margin[y][x] == 0.0)
+        .count();
+
+    let axial = (grid.pitch_x - grid.pitch_y).abs() / ((grid.pitch_x + grid.pitch_y) / 2.0);
+    let grid_deviation = grid_deviation(img, &grid);
+
+    let unused = 1.0 - decoded.errors_corrected as f64 / decoder::MAX_CORRECTABLE as f64;
+
+    let format_errors =
+        (decoded.format_info ^ format_info::encode(decoded.pattern_ref)).count_ones() as usize;
+
+    let mut report = QualityReport {
+        overall: Grade::A,
+        text: Some(decoded.text.clone()),
+        decode_error: None,
+        decode: Parameter {
+            value: 1.0,
+            grade: Grade::A,
+        },
+        symbol_contrast,
+        modulation: Some(modulation),
+        reflectance_margin: Some(reflectance_margin),
+        fixed_pattern_damage: Some(Parameter {
+            value: fixed_pattern_errors as f64,
+            grade: Grade::from_errors(fixed_pattern_errors),
+        }),
+        axial_nonuniformity: Some(Parameter {
+            value: axial,
+            grade: Grade::at_most(axial, [0.06, 0.08, 0.10, 0.12]),
+        }),
+        grid_nonuniformity: Some(Parameter {
+            value: grid_deviation,
+            grade: Grade::at_most(grid_deviation, [0.38, 0.50, 0.63, 0.75]),
+        }),
+        unused_error_correction: Some(Parameter {
+            value: unused,
+            grade: Grade::at_least(unused, [0.62, 0.50, 0.37, 0.25]),
+        }),
+        format_info_damage: Some(Parameter {
+            value: format_errors as f64,
+            grade: Grade::from_errors(format_errors),
+        }),
+    };
+
+    report.overall = report
+        .parameters()
+        .iter()
+        .filter_map(|(_, parameter)| parameter.map(|parameter| parameter.grade))
+        .min()
+        .unwrap();
+
+    report
+}
+
+impl QualityReport {
+    /// Every parameter with its name, in report order.
+    pub fn parameters(&self) -> [(&'static str, Option<&Parameter>); 9] {
+        [
+            ("Decode", Some(&self.decode)),
+            ("Symbol contrast", Some(&self.symbol_contrast)),
+            ("Modulation", self.modulation.as_ref()),
+            ("Reflectance margin", self.reflectance_margin.as_ref()),
+            ("Fixed pattern damage", self.fixed_pattern_damage.as_ref()),
+            ("Axial non-uniformity", self.axial_nonuniformity.as_ref()),
+            ("Grid non-uniformity", self.grid_nonuniformity.as_ref()),
+            (
+                "Unused error correction",
+                self.unused_error_correction.as_ref(),
+            ),
+            (
+                "Format information damage",
+                self.format_info_damage.as_ref(),
+            ),
+        ]
+    }
+
+    pub fn text_report(&self) -> String {
+        let mut report = String::new();
+
+        match (&self.text, &self.decode_error) {
+            (Some(text), _) => report.push_str(&format!("Decoded text: {}\n\n", text)),
+            (_, Some(err)) => report.push_str(&format!("Decode failed: {}\n\n", err)),
+            _ => {}
+        }
+
+        report.push_str(&format!("{:<27} {:>8}  Grade\n", "Parameter", "Value"));
+
+        for (name, parameter) in self.parameters() {
+            let line = match parameter {
+                Some(parameter) => format!(
+                    "{:<27} {:>8}  {}\n",
+                    name,
+                    format_value(name, parameter.value),
+                    parameter.grade
+                ),
+                None => format!("{:<27} {:>8}  -\n", name, "-"),
+            };
+
+            report.push_str(&line);
+        }
+
+        report.push_str(&format!("\nOverall grade: {}\n", self.overall));
+
+        report
+    }
+}
+
+fn format_value(name: &str, value: f64) -> String {
+    match name {
+        "Decode" => String::from(if value > 0.0 { "pass" } else { "fail" }),
+        "Symbol contrast" | "Unused error correction" => format!("{:.0}%", value * 100.0),
+        "Fixed pattern damage" => format!("{:.0}", value),
+        "Format information damage" => format!("{:.0} bits", value),
+        _ => format!("{:.2}", value),
+    }
+}
+
+/// The symbol as it should have been printed, rebuilt from the corrected codewords.
+fn ideal_matrix(decoded: &Decoded) -> CodeMatrix {
+    let data_mask = DataMask::from_pattern_ref(decoded.pattern_ref).unwrap();
+
+    CodeMatrix::with_data(&decoded.codewords)
+        .with_data_mask(&data_mask)
+        .with_format_info(format_info::encode(decoded.pattern_ref))
+}
+
+/// Mean reflectance over the aperture at a module centre, clipped to the image, or `None` if the
+/// aperture lies wholly outside it.
+fn aperture_reflectance(
+    img: &RgbaImage,
+    reflectance: &[f64],
+    grid: &Grid,
+    y: usize,
+    x: usize,
+) -> Option<f64> {
+    let (width, height) = img.dimensions();
+    let (centre_x, centre_y) = grid.centre(y, x);
+    let half_x = (grid.pitch_x * APERTURE / 2.0).max(0.5);
+    let half_y = (grid.pitch_y * APERTURE / 2.0).max(0.5);
+
+    // At least one pixel wide, then clipped to the image
+    let range = |centre: f64, half: f64, limit: u32| {
+        let start = (centre - half).round().max(0.0) as u32;
+        let end = ((centre + half).round().max(0.0) as u32).max(start + 1);
+        start.min(limit)..end.min(limit)
+    };
+
+    let mut sum = 0.0;
+    let mut count = 0;
+
+    for py in range(centre_y, half_y, height) {
+        for px in range(centre_x, half_x, width) {
+            sum += reflectance[(py * width + px) as usize];
+            count += 1;
+        }
+    }
+
+    match count {
+        0 => None,
+        _ => Some(sum / count as f64),
+    }
+}
+
+/// Grades each codeword by its worst module, then finds the best grade for which the codewords
+/// falling below it could all still be corrected.
+fn codeword_parameter(values: &[[f64; MODULE_COUNT]; MODULE_COUNT], bounds: [f64; 4]) -> Parameter {
+    let mut codewords = [f64::INFINITY; CODEWORDS];
+
+    for (y, row) in values.iter().enumerate() {
+        for (x, &value) in row.iter().enumerate() {
+            if let ModuleRole::Codeword { index, .. } = code_matrix::module_role(y, x) {
+                codewords[index] = codewords[index].min(value);
+            }
+        }
+    }
+
+    let grade = [Grade::A, Grade::B, Grade::C, Grade::D]
+        .into_iter()
+        .find(|&grade| {
+            codewords
+                .iter()
+                .filter(|&&value| Grade::at_least(value, bounds) < grade)
+                .count()
+                <= decoder::MAX_CORRECTABLE
+        })
+        .unwrap_or(Grade::F);
+
+    Parameter {
+        value: codewords.iter().cloned().fold(f64::INFINITY, f64::min),
+        grade,
+    }
+}
+
+/// Largest distance of a timing pattern module centre from its place on the grid, in modules.
+fn grid_deviation(img: &RgbaImage, grid: &Grid) -> f64 {
+    let (width, height) = img.dimensions();
+    let reflectance = symbol_reader::reflectance_map(img);
+    let (min, max) = symbol_reader::reflectance_range(&reflectance);
+    let threshold = (min + max) / 2.0;
+    let dark =
+        |x: u32, y: u32| (reflectance[(y * width + x) as usize] < threshold) != grid.reversed;
+
+    let (row_x, row_y) = grid.centre(0, 0);
+    let along_row = symbol_reader::runs((0..width).map(|x| dark(x, row_y as u32)));
+    let down_column = symbol_reader::runs((0..height).map(|y| dark(row_x as u32, y)));
+
+    let deviation = |runs: Vec<(usize, usize)>, origin: f64, pitch: f64| {
+        runs.iter()
+            .map(|&(start, length)| (start as f64 + length as f64 / 2.0 - origin) / pitch)
+            .filter(|&position| (0.0..MODULE_COUNT as f64).contains(&position))
+            .map(|position| {
+                // The finder pattern run is centred on its fourth module
+                let ideal = match position < 7.0 {
+                    true => 3.5,
+                    false => position.floor() + 0.5,
+                };
+
+                (position - ideal).abs()
+            })
+            .fold(0.0, f64::max)
+    };
+
+    deviation(along_row, grid.origin_x, grid.pitch_x).max(deviation(
+        down_column,
+        grid.origin_y,
+        grid.pitch_y,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbol_image::{self, ImageOptions};
+    use crate::{data_codewords, symbol_matrix};
+    use image::Rgba;
+
+    fn image(options: &ImageOptions, text: &str) -> RgbaImage {
+        symbol_image::render(
+            options,
+            &symbol_matrix::generate(&data_codewords::generate(text), None),
+        )
+    }
+
+    #[test]
+    fn test_clean_symbol_grades_a() {
+        let report = grade(&image(&ImageOptions::new(8), "GRADE A"));
+
+        assert_eq!(report.overall, Grade::A);
+        assert_eq!(report.text.as_deref(), Some("GRADE A"));
+        assert_eq!(report.symbol_contrast.value, 1.0);
+        assert_eq!(report.fixed_pattern_damage.unwrap().value, 0.0);
+        assert_eq!(report.unused_error_correction.unwrap().value, 1.0);
+    }
+
+    #[test]
+    fn test_low_contrast_grade() {
+        let options = ImageOptions {
+            foreground: Rgba([150, 150, 150, 255]),
+            background: Rgba([200, 200, 200, 255]),
+            ..ImageOptions::new(8)
+        };
+        let report = grade(&image(&options, "GREY"));
+
+        assert_eq!(report.decode.grade, Grade::A);
+        assert!(report.symbol_contrast.grade <= Grade::D);
+        assert_eq!(report.overall, report.symbol_contrast.grade);
+    }
+
+    #[test]
+    fn test_damage_lowers_grades() {
+        let options = ImageOptions::new(8);
+        let mut img = image(&options, "DAMAGED");
+
+        // Paint out two codeword blocks in the bottom right corner
+        for y in (2 + 13) * 8..(2 + 17) * 8 {
+            for x in (2 + 13) * 8..(2 + 17) * 8 {
+                img.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+
+        let report = grade(&img);
+
+        // Two of the three correctable codewords used up, leaving a third
+        let unused = report.unused_error_correction.unwrap();
+        assert_eq!(report.decode.grade, Grade::A);
+        assert!((unused.value - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(unused.grade, Grade::D);
+        assert_eq!(report.overall, Grade::D);
+    }
+
+    #[test]
+    fn test_blank_image_fails() {
+        let report = grade(&RgbaImage::from_pixel(40, 40, Rgba([255, 255, 255, 255])));
+
+        assert_eq!(report.overall, Grade::F);
+        assert!(report.decode_error.is_some());
+        assert!(report.modulation.is_none());
+        assert!(report.text_report().contains("Overall grade: F"));
+    }
+
+    #[test]
+    fn test_aperture_clipped_to_image() {
+        let img = RgbaImage::from_pixel(20, 20, Rgba([255, 255, 255, 255]));
+        let reflectance = symbol_reader::reflectance_map(&img);
+
+        // A grid running past the right and bottom edges of the image
+        let grid = Grid {
+            origin_x: 0.0,
+            origin_y: 0.0,
+            pitch_x: 2.0,
+            pitch_y: 2.0,
+            reversed: false,
+        };
+
+        assert_eq!(
+            aperture_reflectance(&img, &reflectance, &grid, 0, 0),
+            Some(1.0)
+        );
+        assert_eq!(
+            aperture_reflectance(&img, &reflectance, &grid, 0, 9),
+            Some(1.0)
+        );
+        assert_eq!(
+            aperture_reflectance(&img, &reflectance, &grid, 16, 16),
+            None
+        );
+        assert_eq!(aperture_reflectance(&img, &reflectance, &grid, 0, 10), None);
+    }
+
+    #[test]
+    fn test_grade_bounds() {
+        assert_eq!(Grade::at_least(0.7, [0.70, 0.55, 0.40, 0.20]), Grade::A);
+        assert_eq!(Grade::at_least(0.5, [0.70, 0.55, 0.40, 0.20]), Grade::C);
+        assert_eq!(Grade::at_least(0.1, [0.70, 0.55, 0.40, 0.20]), Grade::F);
+        assert_eq!(Grade::at_most(0.07, [0.06, 0.08, 0.10, 0.12]), Grade::B);
+        assert_eq!(Grade::at_most(0.2, [0.06, 0.08, 0.10, 0.12]), Grade::F);
+    }
+}
@@ -0,0 +1,202 @@
+const MM_PER_INCH: f64 = 25.4;
+const MILS_PER_INCH: f64 = 1000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct XDimension {
+    millimetres: f64,
+}
+
+impl XDimension {
+    pub fn from_mm(millimetres: f64) -> Self {
+        Self { millimetres }
+    }
+
+    pub fn from_mils(mils: f64) -> Self {
+        Self::from_mm(mils / MILS_PER_INCH * MM_PER_INCH)
+    }
+
+    pub fn mm(&self) -> f64 {
+        self.millimetres
+    }
+
+    pub fn mils(&self) -> f64 {
+        self.millimetres / MM_PER_INCH * MILS_PER_INCH
+    }
+
+    /// Width of one module in whole printer dots, never less than a single dot.
+    pub fn module_dots(&self, dpi: u32) -> u32 {
+        let dots = (self.millimetres / MM_PER_INCH * dpi as f64).round() as u32;
+
+        dots.max(1)
+    }
+
+    /// The X-dimension actually printed once the module is snapped to whole dots.
+    pub fn achieved(&self, dpi: u32) -> Self {
+        Self::from_mm(self.module_dots(dpi) as f64 / dpi as f64 * MM_PER_INCH)
+    }
+}
+
+/// Parses an X-dimension such as `0.5mm` or `20mil`.
+pub fn parse_x_dimension(s: &str) -> Result<XDimension, String> {
+    let s = s.trim();
+
+    let (value, unit) = if let Some(value) = s.strip_suffix("mils") {
+        (value, "mil")
+    } else if let Some(value) = s.strip_suffix("mil") {
+        (value, "mil")
+    } else if let Some(value) = s.strip_suffix("mm") {
+        (value, "mm")
+    } else {
+        return Err(String::from(
+            "X-dimension must end with a unit: mm or mil (e.g. 0.5mm, 20mil)",
+        ));
+    };
+
+    let value: f64 = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid X-dimension value: {}", s))?;
+
+    if !(value.is_finite() && value > 0.0) {
+        return Err(String::from("X-dimension must be greater than zero"));
+    }
+
+    match unit {
+        "mm" => Ok(XDimension::from_mm(value)),
+        _ => Ok(XDimension::from_mils(value)),
+    }
+}
+
+/// Page size in millimetres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Parses a page size name (a4, a5, a6, letter, legal) or dimensions such as `100x150mm` or `4x6in`.
+pub fn parse_page_size(s: &str) -> Result<PageSize, String> {
+    let s = s.trim().to_ascii_lowercase();
+
+    let named = match s.as_str() {
+        "a4" => Some((210.0, 297.0)),
+        "a5" => Some((148.0, 210.0)),
+        "a6" => Some((105.0, 148.0)),
+        "letter" => Some((215.9, 279.4)),
+        "legal" => Some((215.9, 355.6)),
+        _ => None,
+    };
+
+    if let Some((width, height)) = named {
+        return Ok(PageSize { width, height });
+    }
+
+    let (dimensions, scale) = if let Some(dimensions) = s.strip_suffix("mm") {
+        (dimensions, 1.0)
+    } else if let Some(dimensions) = s.strip_suffix("in") {
+        (dimensions, MM_PER_INCH)
+    } else {
+        return Err(String::from(
+            "Page size must be a4, a5, a6, letter, legal or WIDTHxHEIGHT in mm or in (e.g. 100x150mm)",
+        ));
+    };
+
+    let (width, height) = dimensions
+        .split_once('x')
+        .and_then(|(width, height)| {
+            Some((
+                width.trim().parse::<f64>().ok()?,
+                height.trim().parse::<f64>().ok()?,
+            ))
+        })
+        .ok_or_else(|| format!("Invalid page size: {}", s))?;
+
+    if !(width.is_finite() && height.is_finite() && width > 0.0 && height > 0.0) {
+        return Err(String::from("Page size must be greater than zero"));
+    }
+
+    Ok(PageSize {
+        width: width * scale,
+        height: height * scale,
+    })
+}
+
+/// Converts a length in millimetres to printer dots, keeping fractions of a dot.
+pub fn mm_to_dots(millimetres: f64, dpi: u32) -> f64 {
+    millimetres / MM_PER_INCH * dpi as f64
+}
+
+pub fn dots_to_mm(dots: f64, dpi: u32) -> f64 {
+    dots / dpi as f64 * MM_PER_INCH
+}
+
+/// Converts dots per inch to the pixels per metre used by PNG and BMP headers.
+pub fn dots_per_metre(dpi: u32) -> u32 {
+    (dpi as f64 * 1000.0 / MM_PER_INCH).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_x_dimension_units() {
+        assert_eq!(parse_x_dimension("0.5mm").unwrap().mm(), 0.5);
+        assert!((parse_x_dimension("20mil").unwrap().mm() - 0.508).abs() < 1e-9);
+        assert!((parse_x_dimension("10 mils").unwrap().mils() - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parse_x_dimension_invalid() {
+        assert!(parse_x_dimension("0.5").is_err());
+        assert!(parse_x_dimension("mm").is_err());
+        assert!(parse_x_dimension("-1mm").is_err());
+        assert!(parse_x_dimension("0mil").is_err());
+    }
+
+    #[test]
+    fn test_module_dots_snaps_to_whole_dots() {
+        // 0.5mm at 203dpi is 3.996 dots
+        let x_dimension = XDimension::from_mm(0.5);
+        assert_eq!(x_dimension.module_dots(203), 4);
+        assert!((x_dimension.achieved(203).mm() - 0.50049).abs() < 1e-4);
+
+        // 10mil at 300dpi is exactly 3 dots
+        let x_dimension = XDimension::from_mils(10.0);
+        assert_eq!(x_dimension.module_dots(300), 3);
+        assert!((x_dimension.achieved(300).mils() - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_module_dots_minimum() {
+        assert_eq!(XDimension::from_mm(0.01).module_dots(72), 1);
+    }
+
+    #[test]
+    fn test_parse_page_size() {
+        assert_eq!(
+            parse_page_size("A4").unwrap(),
+            PageSize {
+                width: 210.0,
+                height: 297.0
+            }
+        );
+        assert_eq!(
+            parse_page_size("100x150mm").unwrap(),
+            PageSize {
+                width: 100.0,
+                height: 150.0
+            }
+        );
+        assert!((parse_page_size("4x6in").unwrap().height - 152.4).abs() < 1e-9);
+        assert!(parse_page_size("100x150").is_err());
+        assert!(parse_page_size("0x150mm").is_err());
+        assert!(parse_page_size("b5").is_err());
+    }
+
+    #[test]
+    fn test_dots_per_metre() {
+        assert_eq!(dots_per_metre(300), 11811);
+        assert_eq!(dots_per_metre(72), 2835);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{alphanumeric_mode, template};
+use clap::ValueEnum;
+
+// Code 39 character set, each character at the index of its mod 43 value
+const MOD43_CHARSET: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ-. $/+%";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CheckCharacter {
+    /// Code 39 modulo 43 check character
+    Mod43,
+    /// Luhn mod N check character over the 45 character alphanumeric set
+    Luhn,
+}
+
+impl CheckCharacter {
+    pub fn compute(self, code: &str) -> Result<char, String> {
+        match self {
+            CheckCharacter::Mod43 => mod43(code),
+            CheckCharacter::Luhn => luhn(code),
+        }
+    }
+}
+
+pub struct Sequence {
+    /// Code template with a `{seq}` or zero padded `{seq:06}` placeholder
+    pub template: String,
+    pub start: u64,
+    pub count: u64,
+    pub step: u64,
+    /// Check character appended to every code
+    pub check: Option<CheckCharacter>,
+}
+
+impl Sequence {
+    /// Every code in the run paired with its sequence number, checked to fit an M4-L symbol.
+    pub fn codes(&self) -> Result<Vec<(u64, String)>, String> {
+        (0..self.count)
+            .map(|i| {
+                let seq = i
+                    .checked_mul(self.step)
+                    .and_then(|offset| offset.checked_add(self.start))
+                    .ok_or_else(|| String::from("Sequence number overflows"))?;
+
+                let mut code = template::expand(&self.template, &[("seq", seq)], &[])?;
+
+                if let Some(check) = self.check {
+                    code.push(check.compute(&code)?);
+                }
+
+                let code = alphanumeric_mode::validate_input(&code)
+                    .map_err(|err| format!("{}: {}", code, err))?;
+
+                Ok((seq, code))
+            })
+            .collect()
+    }
+}
+
+/// Sum of the character values modulo 43, as used by Code 39.
+pub fn mod43(code: &str) -> Result<char, String> {
+    let mut sum = 0;
+
+    for c in code.chars() {
+        let value = MOD43_CHARSET
+            .find(c)
+            .ok_or_else(|| format!("'{}' has no mod 43 check value", c))?;
+        sum += value;
+    }
+
+    Ok(MOD43_CHARSET.as_bytes()[sum % 43] as char)
+}
+
+/// Luhn mod N over the alphanumeric character values, doubling every second value from the right.
+pub fn luhn(code: &str) -> Result<char, String> {
+    let n = alphanumeric_mode::CHARSET.len() as u32;
+    let mut sum = 0;
+
+    for (i, c) in code.chars().rev().enumerate() {
+        let value = alphanumeric_mode::get_alphanumeric_value(c)
+            .ok_or_else(|| format!("'{}' is not in the alphanumeric character set", c))?
+            as u32;
+
+        let addend = if i % 2 == 0 { value * 2 } else { value };
+        sum += addend / n + addend % n;
+    }
+
+    Ok(alphanumeric_mode::CHARSET.as_bytes()[((n - sum % n) % n) as usize] as char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn luhn_valid(code: &str) -> bool {
+        let n = alphanumeric_mode::CHARSET.len() as u32;
+
+        let sum: u32 = code
+            .chars()
+            .rev()
+            .enumerate()
+            .map(|(i, c)| {
+                let value = alphanumeric_mode::get_alphanumeric_value(c).unwrap() as u32;
+                let addend = if i % 2 == 1 { value * 2 } else { value };
+                addend / n + addend % n
+            })
+            .sum();
+
+        sum.is_multiple_of(n)
+    }
+
+    #[test]
+    fn test_mod43() {
+        // 12 + 24 + 13 + 14 + 38 + 3 + 9 = 113, 113 mod 43 = 27
+        assert_eq!(mod43("CODE 39").unwrap(), 'R');
+        assert_eq!(mod43("").unwrap(), '0');
+        assert!(mod43("A*B").is_err());
+    }
+
+    #[test]
+    fn test_luhn() {
+        for code in ["0", "LOT-000001", "A1B2C3", "ZZZ:%"] {
+            let check = luhn(code).unwrap();
+            assert!(luhn_valid(&format!("{}{}", code, check)), "{}", code);
+        }
+
+        // 1 doubled is 2, so the check value is 45 - 2 = 43, '/'
+        assert_eq!(luhn("1").unwrap(), '/');
+        assert!(luhn("a").is_err());
+    }
+
+    #[test]
+    fn test_sequence_codes() {
+        let sequence = Sequence {
+            template: String::from("LOT-{seq:04}"),
+            start: 98,
+            count: 3,
+            step: 5,
+            check: None,
+        };
+
+        assert_eq!(
+            sequence.codes().unwrap(),
+            vec![
+                (98, String::from("LOT-0098")),
+                (103, String::from("LOT-0103")),
+                (108, String::from("LOT-0108")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sequence_appends_check_character() {
+        let sequence = Sequence {
+            template: String::from("CODE {seq}"),
+            start: 39,
+            count: 1,
+            step: 1,
+            check: Some(CheckCharacter::Mod43),
+        };
+
+        assert_eq!(sequence.codes().unwrap()[0].1, "CODE 39R");
+    }
+
+    #[test]
+    fn test_sequence_rejects_codes_that_do_not_fit() {
+        let sequence = Sequence {
+            template: String::from("ABCDEFGHIJKLMNOPQRST{seq}"),
+            start: 9,
+            count: 2,
+            step: 1,
+            check: None,
+        };
+
+        assert!(sequence.codes().is_err());
+    }
+}
@@ -0,0 +1,413 @@
+//! A small HTTP/1.1 service generating symbols on demand, on the standard library's TCP sockets.
+//!
+//! `GET /encode?data=HELLO&format=svg` answers with the symbol. Every other answer is a JSON object
+//! with an `error` field. Each connection carries one request and is closed after the response.
+
+use crate::batch::{self, BatchRow};
+use crate::output_format::OutputFormat;
+use crate::symbol_image::ImageOptions;
+use crate::symbol_output;
+use serde_json::json;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_MODULE_SIZE: u32 = 10;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// Longest request line and headers accepted, in bytes
+    pub max_request_bytes: usize,
+    /// Largest module size a request may ask for, in pixels
+    pub max_module_size: u32,
+    /// Requests handled at once, beyond which new connections are turned away
+    pub max_connections: usize,
+    /// Time allowed for reading the whole request, and for writing its response
+    pub timeout: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_request_bytes: 8 * 1024,
+            max_module_size: 40,
+            max_connections: 16,
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: json!({ "error": message.into() }).to_string().into_bytes(),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            431 => "Request Header Fields Too Large",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        }
+    }
+
+    fn write_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.reason(),
+            self.content_type,
+            self.body.len()
+        )?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+/// Accepts connections until the listener fails, handling each on its own thread.
+pub fn serve(listener: TcpListener, limits: Limits) -> io::Result<()> {
+    let active = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+
+        // Turned away on a thread of its own, so a client slow to take the answer cannot hold up
+        // accepting the next connection
+        if active.fetch_add(1, Ordering::SeqCst) >= limits.max_connections {
+            active.fetch_sub(1, Ordering::SeqCst);
+
+            thread::spawn(move || {
+                let _ = stream.set_write_timeout(Some(limits.timeout));
+                let _ = Response::error(503, "Too many requests at once, try again")
+                    .write_to(&mut stream);
+            });
+
+            continue;
+        }
+
+        let active = Arc::clone(&active);
+
+        thread::spawn(move || {
+            let _ = handle_connection(&mut stream, &limits);
+            active.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_connection(stream: &mut TcpStream, limits: &Limits) -> io::Result<()> {
+    let response = match read_request(stream, limits) {
+        Ok(head) => respond(&head, limits),
+        Err(response) => response,
+    };
+
+    stream.set_write_timeout(Some(limits.timeout))?;
+    response.write_to(stream)
+}
+
+/// The request line and headers, up to the blank line ending them. Each read waits only as long as
+/// is left of the timeout, so a client sending a byte at a time cannot hold the connection open.
+fn read_request(stream: &mut TcpStream, limits: &Limits) -> Result<String, Response> {
+    let max_bytes = limits.max_request_bytes;
+    let deadline = Instant::now() + limits.timeout;
+    let timed_out = || Response::error(408, "Request not received in time");
+
+    let mut head = Vec::new();
+    let mut buffer = [0; 1024];
+
+    while !head.ends_with(b"\r\n\r\n") && !head.ends_with(b"\n\n") {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            return Err(timed_out());
+        }
+
+        stream
+            .set_read_timeout(Some(remaining))
+            .map_err(|_| timed_out())?;
+
+        let read = match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(_) => return Err(timed_out()),
+        };
+
+        head.extend_from_slice(&buffer[..read]);
+
+        if head.len() > max_bytes {
+            return Err(Response::error(
+                431,
+                format!("Request is longer than {} bytes", max_bytes),
+            ));
+        }
+    }
+
+    String::from_utf8(head).map_err(|_| Response::error(400, "Request is not valid UTF-8"))
+}
+
+/// Answers a request from its request line and headers.
+pub fn respond(head: &str, limits: &Limits) -> Response {
+    let mut parts = head.lines().next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Response::error(400, "Malformed request line");
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    match (method, path) {
+        ("GET", "/encode") => encode(query, limits).unwrap_or_else(|err| Response::error(400, err)),
+        (_, "/encode") => Response::error(405, format!("{} is not allowed, use GET", method)),
+        _ => Response::error(404, format!("No such endpoint {}, use /encode", path)),
+    }
+}
+
+/// Generates the symbol a query string asks for.
+fn encode(query: &str, limits: &Limits) -> Result<Response, String> {
+    let mut row = BatchRow {
+        data: String::new(),
+        version: None,
+        ec: None,
+        mask: None,
+        filename: None,
+    };
+    let mut data = None;
+    let mut format = OutputFormat::Svg;
+    let mut options = ImageOptions::new(DEFAULT_MODULE_SIZE);
+
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(value)?;
+
+        match name {
+            "data" => data = Some(value),
+            "ec" => row.ec = Some(value),
+            "version" => row.version = Some(value),
+            "mask" => {
+                row.mask = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Mask must be between 0 and 3, got {}", value))?,
+                )
+            }
+            "format" => {
+                format = match value.to_ascii_lowercase().as_str() {
+                    "svg" => OutputFormat::Svg,
+                    "png" => OutputFormat::Png,
+                    _ => return Err(format!("Unsupported format {}, use svg or png", value)),
+                }
+            }
+            "module_size" => {
+                options.module_size = value
+                    .parse()
+                    .ok()
+                    .filter(|size| (1..=limits.max_module_size).contains(size))
+                    .ok_or_else(|| {
+                        format!(
+                            "Module size must be between 1 and {}, got {}",
+                            limits.max_module_size, value
+                        )
+                    })?
+            }
+            _ => return Err(format!("Unknown parameter {}", name)),
+        }
+    }
+
+    row.data = data.ok_or("Missing parameter data")?;
+    let matrix = batch::symbol(&row)?;
+    let body = symbol_output::encode(format, &options, &matrix).map_err(|err| err.to_string())?;
+
+    Ok(Response {
+        status: 200,
+        content_type: match format {
+            OutputFormat::Png => "image/png",
+            _ => "image/svg+xml",
+        },
+        body,
+    })
+}
+
+/// Decodes `%XX` escapes and `+` for a space in a query string value.
+fn percent_decode(value: &str) -> Result<String, String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let byte = value
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| format!("Invalid escape in {}", value))?;
+                decoded.push(byte);
+                i += 2;
+            }
+            byte => decoded.push(byte),
+        }
+
+        i += 1;
+    }
+
+    String::from_utf8(decoded).map_err(|_| format!("{} is not valid UTF-8", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbol_reader;
+    use std::net::Shutdown;
+
+    fn get(target: &str) -> Response {
+        respond(
+            &format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target),
+            &Limits::default(),
+        )
+    }
+
+    fn error(response: &Response) -> String {
+        let body: serde_json::Value = serde_json::from_slice(&response.body).unwrap();
+        body["error"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_encode_svg_and_png() {
+        let svg = get("/encode?data=HELLO%20WORLD");
+        assert_eq!(svg.status, 200);
+        assert_eq!(svg.content_type, "image/svg+xml");
+        assert!(String::from_utf8(svg.body).unwrap().starts_with("<svg"));
+
+        let png = get("/encode?data=HELLO+WORLD&format=png&ec=L&module_size=4");
+        assert_eq!(png.content_type, "image/png");
+
+        let img = image::load_from_memory(&png.body).unwrap().to_rgba8();
+        assert_eq!(img.width(), 4 * (17 + 4));
+        assert_eq!(
+            symbol_reader::decode_image(&img).unwrap().text,
+            "HELLO WORLD"
+        );
+    }
+
+    #[test]
+    fn test_errors_are_json() {
+        let invalid = get("/encode?data=hello");
+        assert_eq!(invalid.status, 400);
+        assert_eq!(invalid.content_type, "application/json");
+        assert!(error(&invalid).contains("invalid characters"));
+
+        assert!(error(&get("/encode?data=A&ec=M")).contains("only L is supported"));
+        assert!(error(&get("/encode?data=A&format=gif")).contains("use svg or png"));
+        assert!(error(&get("/encode?data=A&module_size=500")).contains("between 1 and 40"));
+        assert_eq!(error(&get("/encode?format=png")), "Missing parameter data");
+        assert_eq!(get("/decode?data=A").status, 404);
+        assert_eq!(
+            respond("POST /encode HTTP/1.1\r\n\r\n", &Limits::default()).status,
+            405
+        );
+    }
+
+    /// Reads a request sent over a local connection by a client that then stops writing.
+    fn read_sent(request: &[u8], limits: &Limits) -> Result<String, Response> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.write_all(request).unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+
+        let (mut stream, _) = listener.accept().unwrap();
+        read_request(&mut stream, limits)
+    }
+
+    #[test]
+    fn test_request_size_limit() {
+        let mut request = format!("GET /encode?data={} HTTP/1.1\r\n\r\n", "A".repeat(100));
+        let limits = |max_request_bytes| Limits {
+            max_request_bytes,
+            ..Limits::default()
+        };
+
+        assert_eq!(
+            read_sent(request.as_bytes(), &limits(64))
+                .unwrap_err()
+                .status,
+            431
+        );
+        assert!(read_sent(request.as_bytes(), &limits(1024)).is_ok());
+
+        request.truncate(20);
+        assert!(read_sent(request.as_bytes(), &limits(1024)).is_ok());
+    }
+
+    #[test]
+    fn test_request_timeout_covers_the_whole_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let limits = Limits {
+            timeout: Duration::from_millis(300),
+            ..Limits::default()
+        };
+
+        // One byte every 50 ms, each arriving well within the timeout
+        thread::spawn(move || {
+            for byte in b"GET /encode?data=SLOW HTTP/1.1\r\n\r\n" {
+                if client.write_all(&[*byte]).is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+
+        let (mut stream, _) = listener.accept().unwrap();
+        let start = Instant::now();
+
+        assert_eq!(read_request(&mut stream, &limits).unwrap_err().status, 408);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_serve_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener, Limits::default()));
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(b"GET /encode?data=TCP HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Content-Type: image/svg+xml\r\n"));
+        assert!(response.ends_with("</svg>\n") || response.ends_with("</svg>"));
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("A%2BB+C%3a").unwrap(), "A+B C:");
+        assert!(percent_decode("%4").is_err());
+        assert!(percent_decode("%FF").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! Module matrix and encoding metadata as JSON or CSV, for tools that draw or audit symbols themselves.
+
+use crate::code_matrix::CodeMatrix;
+use crate::decoder;
+use serde::Serialize;
+
+/// Bumped whenever a field is renamed, removed or changes meaning.
+pub const SCHEMA_VERSION: u32 = 1;
+
+const SIZE: usize = 17;
+
+#[derive(Debug, Serialize)]
+pub struct SymbolData {
+    pub schema_version: u32,
+    pub version: &'static str,
+    pub size: usize,
+    pub ec_level: &'static str,
+    pub mask: u8,
+    /// The 15 bit format information word, after masking
+    pub format_info: u16,
+    pub data: String,
+    pub data_codewords: Vec<u8>,
+    pub ec_codewords: Vec<u8>,
+    /// Rows from the top, 1 for a dark module. The quiet zone is not included
+    pub modules: Vec<Vec<u8>>,
+}
+
+impl SymbolData {
+    pub fn new(matrix: &CodeMatrix) -> Self {
+        // Every generated symbol decodes, so this reads back what the encoder chose
+        let decoded = decoder::decode(matrix).unwrap();
+        let (data_codewords, ec_codewords) = decoded.codewords.split_at(decoder::DATA_CODEWORDS);
+
+        Self {
+            schema_version: SCHEMA_VERSION,
+            version: "M4",
+            size: SIZE,
+            ec_level: "L",
+            mask: decoded.pattern_ref,
+            format_info: decoded.format_info,
+            data: decoded.text,
+            data_codewords: data_codewords.to_vec(),
+            ec_codewords: ec_codewords.to_vec(),
+            modules: (0..SIZE)
+                .map(|y| (0..SIZE).map(|x| matrix.read(y, x)).collect())
+                .collect(),
+        }
+    }
+}
+
+pub fn json(matrix: &CodeMatrix) -> String {
+    serde_json::to_string_pretty(&SymbolData::new(matrix)).unwrap() + "\n"
+}
+
+/// Field and value pairs under a `field,value` header. Codewords are space separated hex bytes,
+/// the format word is hex and each module row is a string of 0 and 1.
+pub fn csv(matrix: &CodeMatrix) -> Vec<u8> {
+    let data = SymbolData::new(matrix);
+    let hex = |codewords: &[u8]| {
+        codewords
+            .iter()
+            .map(|codeword| format!("{:02X}", codeword))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut fields = vec![
+        (
+            String::from("schema_version"),
+            data.schema_version.to_string(),
+        ),
+        (String::from("version"), data.version.to_string()),
+        (String::from("size"), data.size.to_string()),
+        (String::from("ec_level"), data.ec_level.to_string()),
+        (String::from("mask"), data.mask.to_string()),
+        (
+            String::from("format_info"),
+            format!("0x{:04X}", data.format_info),
+        ),
+        (String::from("data"), data.data.clone()),
+        (String::from("data_codewords"), hex(&data.data_codewords)),
+        (String::from("ec_codewords"), hex(&data.ec_codewords)),
+    ];
+
+    for (y, row) in data.modules.iter().enumerate() {
+        let modules = row.iter().map(|module| module.to_string()).collect();
+        fields.push((format!("row_{:02}", y), modules));
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["field", "value"]).unwrap();
+
+    for (field, value) in fields {
+        writer.write_record([field, value]).unwrap();
+    }
+
+    writer.into_inner().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_mask::DataMask;
+    use crate::{data_codewords, symbol_matrix};
+
+    fn encode(text: &str) -> CodeMatrix {
+        symbol_matrix::generate(
+            &data_codewords::generate(text),
+            Some(DataMask::pattern_01()),
+        )
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let value: serde_json::Value = serde_json::from_str(&json(&encode("HELLO"))).unwrap();
+
+        let keys: Vec<_> = value.as_object().unwrap().keys().cloned().collect();
+        assert_eq!(
+            keys,
+            [
+                "data",
+                "data_codewords",
+                "ec_codewords",
+                "ec_level",
+                "format_info",
+                "mask",
+                "modules",
+                "schema_version",
+                "size",
+                "version"
+            ]
+        );
+
+        assert_eq!(value["data"], "HELLO");
+        assert_eq!(value["mask"], 1);
+        assert_eq!(value["format_info"], 0x1202);
+        assert_eq!(value["data_codewords"].as_array().unwrap().len(), 16);
+        assert_eq!(value["ec_codewords"].as_array().unwrap().len(), 8);
+        assert_eq!(value["modules"][0][0], 1);
+        assert_eq!(value["modules"][1][1], 0);
+    }
+
+    #[test]
+    fn test_csv_fields() {
+        let text = String::from_utf8(csv(&encode("HELLO"))).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+
+        assert_eq!(lines[0], "field,value");
+        assert_eq!(lines[5], "mask,1");
+        assert_eq!(lines[6], "format_info,0x1202");
+        assert_eq!(lines[10], "row_00,11111110101010101");
+        assert_eq!(lines.len(), 10 + SIZE);
+    }
+}
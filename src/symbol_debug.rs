@@ -0,0 +1,371 @@
+//! Symbol drawn with every module coloured by its role, for teaching and for diagnosing placement bugs.
+
+use crate::code_matrix::{self, CodeMatrix, ModuleRole};
+use crate::colour;
+use crate::hri_font;
+use crate::symbol_svg;
+use image::{Rgba, RgbaImage};
+use std::fmt::Write;
+
+const MODULE_COUNT: usize = 17;
+const DATA_CODEWORDS: usize = 16;
+
+const FINDER: (Rgba<u8>, Rgba<u8>) = (Rgba([150, 30, 30, 255]), Rgba([240, 200, 200, 255]));
+const SEPARATOR: (Rgba<u8>, Rgba<u8>) = (Rgba([90, 90, 90, 255]), Rgba([225, 225, 225, 255]));
+const TIMING: (Rgba<u8>, Rgba<u8>) = (Rgba([200, 110, 0, 255]), Rgba([250, 225, 180, 255]));
+const FORMAT_INFO: (Rgba<u8>, Rgba<u8>) = (Rgba([100, 40, 150, 255]), Rgba([225, 205, 240, 255]));
+
+// Lines between modules of different codewords or roles
+const OUTLINE: Rgba<u8> = Rgba([40, 40, 40, 255]);
+
+#[derive(Debug, Clone, Copy)]
+pub struct DebugOptions {
+    pub module_size: u32,
+    pub quiet_zone: u32,
+    /// Label every codeword module with its codeword and bit, and format modules with their bit
+    pub labels: bool,
+}
+
+/// The colour of a module by role, a strong shade when dark and a pale one when light. Data
+/// codewords take evenly spaced hues and error correction codewords muted ones.
+pub fn role_colour(role: ModuleRole, dark: bool) -> Rgba<u8> {
+    let (dark_colour, light_colour) = match role {
+        ModuleRole::Finder => FINDER,
+        ModuleRole::Separator => SEPARATOR,
+        ModuleRole::Timing => TIMING,
+        ModuleRole::FormatInfo(_) => FORMAT_INFO,
+        ModuleRole::Codeword { index, .. } if index < DATA_CODEWORDS => {
+            let hue = index as f64 * 360.0 / DATA_CODEWORDS as f64;
+            (hsl(hue, 0.75, 0.38), hsl(hue, 0.75, 0.85))
+        }
+        ModuleRole::Codeword { index, .. } => {
+            let hue = (index - DATA_CODEWORDS) as f64 * 45.0 + 200.0;
+            (hsl(hue, 0.2, 0.3), hsl(hue, 0.2, 0.82))
+        }
+    };
+
+    if dark {
+        dark_colour
+    } else {
+        light_colour
+    }
+}
+
+/// Short codeword name, `D0` to `D15` for data and `E0` to `E7` for error correction codewords.
+pub fn codeword_name(index: usize) -> String {
+    match index {
+        index if index < DATA_CODEWORDS => format!("D{}", index),
+        index => format!("E{}", index - DATA_CODEWORDS),
+    }
+}
+
+fn description(role: ModuleRole) -> String {
+    match role {
+        ModuleRole::Finder => String::from("Finder pattern"),
+        ModuleRole::Separator => String::from("Separator"),
+        ModuleRole::Timing => String::from("Timing pattern"),
+        ModuleRole::FormatInfo(bit) => format!("Format information bit {}", bit),
+        ModuleRole::Codeword { index, bit } if index < DATA_CODEWORDS => {
+            format!("Data codeword {}, bit {}", index, bit)
+        }
+        ModuleRole::Codeword { index, bit } => {
+            format!(
+                "Error correction codeword {}, bit {}",
+                index - DATA_CODEWORDS,
+                bit
+            )
+        }
+    }
+}
+
+/// Label lines for a module, when labels are drawn.
+fn label(role: ModuleRole) -> Vec<String> {
+    match role {
+        ModuleRole::Codeword { index, bit } => vec![codeword_name(index), bit.to_string()],
+        ModuleRole::FormatInfo(bit) => vec![String::from("F"), bit.to_string()],
+        _ => Vec::new(),
+    }
+}
+
+/// Whether the modules either side of an edge belong to different patterns or codewords.
+fn boundary(a: ModuleRole, b: ModuleRole) -> bool {
+    match (a, b) {
+        (ModuleRole::Codeword { index: a, .. }, ModuleRole::Codeword { index: b, .. }) => a != b,
+        (ModuleRole::FormatInfo(_), ModuleRole::FormatInfo(_)) => false,
+        _ => std::mem::discriminant(&a) != std::mem::discriminant(&b),
+    }
+}
+
+fn label_colour(dark: bool) -> Rgba<u8> {
+    if dark {
+        colour::WHITE
+    } else {
+        colour::BLACK
+    }
+}
+
+pub fn svg(options: &DebugOptions, matrix: &CodeMatrix) -> String {
+    let size = options.module_size;
+    let side = (MODULE_COUNT as u32 + 2 * options.quiet_zone) * size;
+    let offset = options.quiet_zone * size;
+    let mut svg = String::new();
+
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{0}" height="{0}" viewBox="0 0 {0} {0}">"#,
+        side
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<rect width="{0}" height="{0}" {1}/>"#,
+        side,
+        symbol_svg::fill(colour::WHITE)
+    )
+    .unwrap();
+
+    for y in 0..MODULE_COUNT {
+        for x in 0..MODULE_COUNT {
+            let role = code_matrix::module_role(y, x);
+            let dark = matrix.read(y, x) == 1;
+
+            writeln!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" {}><title>{}</title></rect>"#,
+                offset + x as u32 * size,
+                offset + y as u32 * size,
+                size,
+                size,
+                symbol_svg::fill(role_colour(role, dark)),
+                description(role)
+            )
+            .unwrap();
+        }
+    }
+
+    for (x1, y1, x2, y2) in outline_edges() {
+        writeln!(
+            svg,
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}"/>"#,
+            offset + x1 * size,
+            offset + y1 * size,
+            offset + x2 * size,
+            offset + y2 * size,
+            hex(OUTLINE),
+            symbol_svg::number((size as f64 / 16.0).max(1.0))
+        )
+        .unwrap();
+    }
+
+    if options.labels {
+        let font_size = size as f64 / 3.0;
+
+        for y in 0..MODULE_COUNT {
+            for x in 0..MODULE_COUNT {
+                let lines = label(code_matrix::module_role(y, x));
+                let dark = matrix.read(y, x) == 1;
+                let centre_x = offset as f64 + (x as f64 + 0.5) * size as f64;
+
+                for (i, line) in lines.iter().enumerate() {
+                    let baseline = offset as f64
+                        + y as f64 * size as f64
+                        + size as f64 * (0.45 + 0.4 * i as f64);
+
+                    writeln!(
+                        svg,
+                        r#"<text x="{}" y="{}" font-family="sans-serif" font-size="{}" text-anchor="middle" fill="{}">{}</text>"#,
+                        symbol_svg::number(centre_x),
+                        symbol_svg::number(baseline),
+                        symbol_svg::number(font_size),
+                        hex(label_colour(dark)),
+                        line
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+pub fn raster(options: &DebugOptions, matrix: &CodeMatrix) -> RgbaImage {
+    let size = options.module_size;
+    let side = (MODULE_COUNT as u32 + 2 * options.quiet_zone) * size;
+    let offset = options.quiet_zone * size;
+    let mut img = RgbaImage::from_pixel(side, side, colour::WHITE);
+
+    for y in 0..MODULE_COUNT {
+        for x in 0..MODULE_COUNT {
+            let fill = role_colour(code_matrix::module_role(y, x), matrix.read(y, x) == 1);
+
+            for py in 0..size {
+                for px in 0..size {
+                    img.put_pixel(
+                        offset + x as u32 * size + px,
+                        offset + y as u32 * size + py,
+                        fill,
+                    );
+                }
+            }
+        }
+    }
+
+    // Lines are drawn on the first pixel past each edge, and on the last one at the far side
+    let line =
+        |position: u32| (offset + position * size).min(offset + MODULE_COUNT as u32 * size - 1);
+
+    for (x1, y1, x2, y2) in outline_edges() {
+        if x1 == x2 {
+            for py in offset + y1 * size..offset + y2 * size {
+                img.put_pixel(line(x1), py, OUTLINE);
+            }
+        } else {
+            for px in offset + x1 * size..offset + x2 * size {
+                img.put_pixel(px, line(y1), OUTLINE);
+            }
+        }
+    }
+
+    if options.labels {
+        let font_size = size / 4;
+        let line_height = hri_font::text_height(font_size);
+
+        for y in 0..MODULE_COUNT {
+            for x in 0..MODULE_COUNT {
+                let lines = label(code_matrix::module_role(y, x));
+                let ink = label_colour(matrix.read(y, x) == 1);
+                let top = size.saturating_sub(line_height * lines.len() as u32 + 2) / 2;
+
+                for (i, text) in lines.iter().enumerate() {
+                    let left = size.saturating_sub(hri_font::text_width(text, font_size)) / 2;
+                    let origin_x = offset + x as u32 * size + left;
+                    let origin_y = offset + y as u32 * size + top + i as u32 * (line_height + 2);
+
+                    hri_font::draw(text, font_size, |px, py| {
+                        let (px, py) = (origin_x + px, origin_y + py);
+
+                        if px < side && py < side {
+                            img.put_pixel(px, py, ink);
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    img
+}
+
+/// Module edges, in module units from the symbol corner, where the role changes from one side to
+/// the other, plus the outline of the symbol. Each is a vertical or horizontal unit segment.
+fn outline_edges() -> Vec<(u32, u32, u32, u32)> {
+    let n = MODULE_COUNT;
+    let role = |y: usize, x: usize| code_matrix::module_role(y, x);
+    let mut edges = Vec::new();
+
+    for y in 0..n {
+        for x in 0..=n {
+            if x == 0 || x == n || boundary(role(y, x - 1), role(y, x)) {
+                edges.push((x as u32, y as u32, x as u32, y as u32 + 1));
+            }
+        }
+    }
+
+    for y in 0..=n {
+        for x in 0..n {
+            if y == 0 || y == n || boundary(role(y - 1, x), role(y, x)) {
+                edges.push((x as u32, y as u32, x as u32 + 1, y as u32));
+            }
+        }
+    }
+
+    edges
+}
+
+fn hex(colour: Rgba<u8>) -> String {
+    format!("#{:02x}{:02x}{:02x}", colour[0], colour[1], colour[2])
+}
+
+fn hsl(hue: f64, saturation: f64, lightness: f64) -> Rgba<u8> {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let h = (hue % 360.0) / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    let m = lightness - chroma / 2.0;
+    let channel = |value: f64| ((value + m) * 255.0).round() as u8;
+
+    Rgba([channel(r), channel(g), channel(b), 255])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data_codewords, symbol_matrix};
+
+    fn options(labels: bool) -> DebugOptions {
+        DebugOptions {
+            module_size: 32,
+            quiet_zone: 1,
+            labels,
+        }
+    }
+
+    #[test]
+    fn test_raster_colours_modules_by_role() {
+        let matrix = symbol_matrix::generate(&data_codewords::generate("DEBUG"), None);
+        let img = raster(&options(false), &matrix);
+
+        assert_eq!(img.dimensions(), (19 * 32, 19 * 32));
+
+        // Centre of the finder pattern, and of the module holding bit 7 of the first codeword
+        assert_eq!(*img.get_pixel(4 * 32 + 16, 4 * 32 + 16), FINDER.0);
+
+        let first = role_colour(
+            ModuleRole::Codeword { index: 0, bit: 7 },
+            matrix.read(16, 16) == 1,
+        );
+        assert_eq!(*img.get_pixel(17 * 32 + 16, 17 * 32 + 16), first);
+    }
+
+    #[test]
+    fn test_svg_labels() {
+        let matrix = symbol_matrix::generate(&data_codewords::generate("DEBUG"), None);
+
+        let plain = svg(&options(false), &matrix);
+        assert!(plain.contains("<title>Error correction codeword 7, bit 0</title>"));
+        assert!(!plain.contains("<text"));
+
+        let labelled = svg(&options(true), &matrix);
+        assert!(labelled.contains(">D15</text>"));
+        assert!(labelled.contains(">E7</text>"));
+    }
+
+    #[test]
+    fn test_codeword_colours_differ() {
+        let colours: Vec<_> = (0..24)
+            .map(|index| role_colour(ModuleRole::Codeword { index, bit: 0 }, true))
+            .collect();
+
+        for (i, a) in colours.iter().enumerate() {
+            assert!(colours[i + 1..].iter().all(|b| a != b));
+        }
+    }
+
+    #[test]
+    fn test_hsl() {
+        assert_eq!(hsl(0.0, 1.0, 0.5), Rgba([255, 0, 0, 255]));
+        assert_eq!(hsl(240.0, 1.0, 0.5), Rgba([0, 0, 255, 255]));
+    }
+}
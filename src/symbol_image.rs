@@ -1,46 +1,634 @@
 use crate::code_matrix::CodeMatrix;
-use image::{ImageBuffer, Rgb};
+use crate::output_format::OutputFormat;
+use crate::{colour, hri_font, print_gain, print_size};
+use image::codecs::bmp::BmpEncoder;
+use image::codecs::gif::GifEncoder;
+use image::codecs::jpeg::{JpegEncoder, PixelDensity};
+use image::codecs::webp::WebPEncoder;
+use image::error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use image::{DynamicImage, ExtendedColorType, ImageBuffer, ImageError, Rgba, RgbaImage};
+use std::io::Cursor;
 
-const SYMBOL_SIZE: usize = 21;
 const MODULE_COUNT: usize = 17;
-const QUIET_ZONE: usize = 2;
+pub const QUIET_ZONE: u32 = 2;
 
-pub fn write(file_name: &str, module_size: u32, matrix: &CodeMatrix) {
-    let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(
-        SYMBOL_SIZE as u32 * module_size,
-        SYMBOL_SIZE as u32 * module_size,
-    );
+// Offset of the horizontal resolution field in a BMP file header + BITMAPINFOHEADER
+const BMP_RESOLUTION_OFFSET: usize = 38;
 
-    img.fill(255);
+#[derive(Clone)]
+pub struct ImageOptions {
+    pub module_size: u32,
+    /// Quiet zone width in modules
+    pub quiet_zone: u32,
+    pub foreground: Rgba<u8>,
+    pub background: Rgba<u8>,
+    pub dpi: Option<u32>,
+    /// Bar width reduction in pixels, taken off every dark region to offset ink spread
+    pub print_gain: f64,
+    /// Position of the symbol on the label in printer dots, used by the label printer outputs
+    pub offset: (u32, u32),
+    /// Human readable interpretation printed under the symbol
+    pub hri: Option<Hri>,
+}
+
+#[derive(Clone)]
+pub struct Hri {
+    pub text: String,
+    /// Text height in pixels
+    pub font_size: u32,
+    /// Gap between the quiet zone and the text, and below the text, in pixels
+    pub spacing: u32,
+}
+
+/// Placement of the symbol and text on the canvas, shared by all renderers so their output lines up.
+#[derive(Debug, PartialEq)]
+pub struct Layout {
+    pub width: u32,
+    pub height: u32,
+    /// Left edge of the symbol including its quiet zone
+    pub symbol_x: u32,
+    /// Width and height of the symbol including its quiet zone
+    pub symbol_size: u32,
+    /// Top edge of the text
+    pub text_y: u32,
+    pub text_height: u32,
+}
+
+impl ImageOptions {
+    pub fn new(module_size: u32) -> Self {
+        Self {
+            module_size,
+            quiet_zone: QUIET_ZONE,
+            foreground: colour::BLACK,
+            background: colour::WHITE,
+            dpi: None,
+            print_gain: 0.0,
+            offset: (0, 0),
+            hri: None,
+        }
+    }
+
+    pub fn layout(&self) -> Layout {
+        let symbol_size = (MODULE_COUNT as u32 + 2 * self.quiet_zone) * self.module_size;
+
+        let Some(hri) = &self.hri else {
+            return Layout {
+                width: symbol_size,
+                height: symbol_size,
+                symbol_x: 0,
+                symbol_size,
+                text_y: symbol_size,
+                text_height: 0,
+            };
+        };
+
+        let text_width = hri_font::text_width(&hri.text, hri.font_size) + 2 * hri.spacing;
+        let text_height = hri_font::text_height(hri.font_size);
+        let width = symbol_size.max(text_width);
+
+        Layout {
+            width,
+            height: symbol_size + 2 * hri.spacing + text_height,
+            symbol_x: (width - symbol_size) / 2,
+            symbol_size,
+            text_y: symbol_size + hri.spacing,
+            text_height,
+        }
+    }
+
+    /// Light modules on a dark background, as used for laser marking on metal.
+    pub fn with_reversed_reflectance(self) -> Self {
+        Self {
+            foreground: self.background,
+            background: self.foreground,
+            ..self
+        }
+    }
+
+    /// Sets the human readable text, if the options ask for it to be printed.
+    pub fn with_hri_text(self, text: &str) -> Self {
+        Self {
+            hri: self.hri.map(|hri| Hri {
+                text: text.to_string(),
+                ..hri
+            }),
+            ..self
+        }
+    }
+
+    pub fn symbol_contrast(&self) -> f64 {
+        colour::symbol_contrast(self.foreground, self.background)
+    }
+}
+
+/// Renders the symbol and encodes it in the given raster format.
+pub fn encode(
+    format: OutputFormat,
+    options: &ImageOptions,
+    matrix: &CodeMatrix,
+) -> Result<Vec<u8>, ImageError> {
+    encode_image(format, &render(options, matrix), options.dpi)
+}
+
+/// Encodes an already rendered image in the given raster format.
+pub fn encode_image(
+    format: OutputFormat,
+    img: &RgbaImage,
+    dpi: Option<u32>,
+) -> Result<Vec<u8>, ImageError> {
+    match format {
+        OutputFormat::Png => encode_png(img, dpi),
+        OutputFormat::MonoPng => encode_mono_png(img, dpi),
+        OutputFormat::Pbm => Ok(encode_pbm(img)),
+        OutputFormat::Pgm => Ok(encode_pgm(img)),
+        OutputFormat::Bmp => encode_bmp(img, dpi),
+        OutputFormat::Tiff => encode_tiff(img, dpi),
+        OutputFormat::Gif => encode_gif(img),
+        OutputFormat::Webp => encode_webp(img),
+        OutputFormat::Jpeg => encode_jpeg(img, dpi),
+        OutputFormat::Svg
+        | OutputFormat::Pdf
+        | OutputFormat::Zpl
+        | OutputFormat::Epl
+        | OutputFormat::Escpos
+        | OutputFormat::Text
+        | OutputFormat::Json
+        | OutputFormat::Csv => Err(ImageError::Unsupported(
+            UnsupportedError::from_format_and_kind(
+                ImageFormatHint::Unknown,
+                UnsupportedErrorKind::GenericFeature(format!(
+                    "{:?} is not a raster format",
+                    format
+                )),
+            ),
+        )),
+    }
+}
+
+/// One bit per dot, rows packed most significant bit first and padded to whole bytes.
+pub struct Bitmap {
+    pub height: u32,
+    pub row_bytes: usize,
+    /// Set bits are dark dots
+    pub data: Vec<u8>,
+}
+
+/// Renders the symbol as a 1-bit bitmap for printers that take raw dot data.
+pub fn bitmap(options: &ImageOptions, matrix: &CodeMatrix) -> Bitmap {
+    let img = render(options, matrix);
+
+    Bitmap {
+        height: img.height(),
+        row_bytes: img.width().div_ceil(8) as usize,
+        data: pack_bits(&img, is_dark),
+    }
+}
+
+pub fn render(options: &ImageOptions, matrix: &CodeMatrix) -> RgbaImage {
+    let layout = options.layout();
+    let mut img = ImageBuffer::from_pixel(layout.width, layout.height, options.background);
 
     for i in 0..MODULE_COUNT {
         for j in 0..MODULE_COUNT {
-            let module_value = matrix.read(i, j);
-            put_module(&mut img, module_size, module_value, j, i);
+            if matrix.read(i, j) == 1 {
+                put_module(&mut img, options, &layout, matrix, j, i);
+            }
+        }
+    }
+
+    if let Some(hri) = &options.hri {
+        let text_x = (layout.width - hri_font::text_width(&hri.text, hri.font_size)) / 2;
+
+        hri_font::draw(&hri.text, hri.font_size, |x, y| {
+            img.put_pixel(text_x + x, layout.text_y + y, options.foreground)
+        });
+    }
+
+    img
+}
+
+fn encode_png(img: &RgbaImage, dpi: Option<u32>) -> Result<Vec<u8>, ImageError> {
+    encode_png_data(
+        img.width(),
+        img.height(),
+        png::ColorType::Rgba,
+        png::BitDepth::Eight,
+        img.as_raw(),
+        dpi,
+    )
+}
+
+/// Single bit grayscale, each pixel thresholded at mid reflectance so reversed symbols stay reversed.
+fn encode_mono_png(img: &RgbaImage, dpi: Option<u32>) -> Result<Vec<u8>, ImageError> {
+    let data = pack_bits(img, |pixel| !is_dark(pixel));
+
+    encode_png_data(
+        img.width(),
+        img.height(),
+        png::ColorType::Grayscale,
+        png::BitDepth::One,
+        &data,
+        dpi,
+    )
+}
+
+fn encode_png_data(
+    width: u32,
+    height: u32,
+    color: png::ColorType,
+    depth: png::BitDepth,
+    data: &[u8],
+    dpi: Option<u32>,
+) -> Result<Vec<u8>, ImageError> {
+    let mut bytes = Vec::new();
+
+    let mut encoder = png::Encoder::new(&mut bytes, width, height);
+    encoder.set_color(color);
+    encoder.set_depth(depth);
+
+    if let Some(dpi) = dpi {
+        let ppm = print_size::dots_per_metre(dpi);
+
+        encoder.set_pixel_dims(Some(png::PixelDimensions {
+            xppu: ppm,
+            yppu: ppm,
+            unit: png::Unit::Meter,
+        }));
+    }
+
+    let mut writer = encoder.write_header().map_err(to_image_error)?;
+    writer.write_image_data(data).map_err(to_image_error)?;
+    writer.finish().map_err(to_image_error)?;
+
+    Ok(bytes)
+}
+
+/// Binary PBM, where a set bit is a dark pixel.
+fn encode_pbm(img: &RgbaImage) -> Vec<u8> {
+    let mut bytes = format!("P4\n{} {}\n", img.width(), img.height()).into_bytes();
+    bytes.extend(pack_bits(img, is_dark));
+
+    bytes
+}
+
+/// Binary 8-bit PGM holding the luminance of each pixel.
+fn encode_pgm(img: &RgbaImage) -> Vec<u8> {
+    let mut bytes = format!("P5\n{} {}\n255\n", img.width(), img.height()).into_bytes();
+    bytes.extend(DynamicImage::ImageRgba8(flatten(img)).to_luma8().into_raw());
+
+    bytes
+}
+
+fn encode_tiff(img: &RgbaImage, dpi: Option<u32>) -> Result<Vec<u8>, ImageError> {
+    use tiff::encoder::{colortype, Rational, TiffEncoder};
+    use tiff::tags::ResolutionUnit;
+
+    let mut bytes = Cursor::new(Vec::new());
+
+    let mut encoder = TiffEncoder::new(&mut bytes).map_err(to_image_error)?;
+    let mut image = encoder
+        .new_image::<colortype::RGBA8>(img.width(), img.height())
+        .map_err(to_image_error)?;
+
+    if let Some(dpi) = dpi {
+        image.resolution(ResolutionUnit::Inch, Rational { n: dpi, d: 1 });
+    }
+
+    image.write_data(img.as_raw()).map_err(to_image_error)?;
+
+    Ok(bytes.into_inner())
+}
+
+fn encode_bmp(img: &RgbaImage, dpi: Option<u32>) -> Result<Vec<u8>, ImageError> {
+    let mut bytes = Vec::new();
+
+    BmpEncoder::new(&mut bytes).encode(
+        img.as_raw(),
+        img.width(),
+        img.height(),
+        ExtendedColorType::Rgba8,
+    )?;
+
+    // The BMP encoder always leaves the resolution blank, so patch it in afterwards.
+    // The field sits at the same offset in both the BITMAPINFOHEADER and BITMAPV4HEADER layouts
+    if let Some(dpi) = dpi {
+        let ppm = print_size::dots_per_metre(dpi).to_le_bytes();
+        bytes[BMP_RESOLUTION_OFFSET..BMP_RESOLUTION_OFFSET + 4].copy_from_slice(&ppm);
+        bytes[BMP_RESOLUTION_OFFSET + 4..BMP_RESOLUTION_OFFSET + 8].copy_from_slice(&ppm);
+    }
+
+    Ok(bytes)
+}
+
+fn encode_gif(img: &RgbaImage) -> Result<Vec<u8>, ImageError> {
+    let mut bytes = Vec::new();
+
+    GifEncoder::new(&mut bytes).encode(
+        img.as_raw(),
+        img.width(),
+        img.height(),
+        ExtendedColorType::Rgba8,
+    )?;
+
+    Ok(bytes)
+}
+
+fn encode_webp(img: &RgbaImage) -> Result<Vec<u8>, ImageError> {
+    let mut bytes = Vec::new();
+
+    WebPEncoder::new_lossless(&mut bytes).encode(
+        img.as_raw(),
+        img.width(),
+        img.height(),
+        ExtendedColorType::Rgba8,
+    )?;
+
+    Ok(bytes)
+}
+
+fn encode_jpeg(img: &RgbaImage, dpi: Option<u32>) -> Result<Vec<u8>, ImageError> {
+    let mut bytes = Vec::new();
+
+    // JPEG has no alpha channel
+    let rgb = DynamicImage::ImageRgba8(flatten(img)).to_rgb8();
+
+    let mut encoder = JpegEncoder::new_with_quality(&mut bytes, 95);
+
+    if let Some(dpi) = dpi {
+        encoder.set_pixel_density(PixelDensity::dpi(dpi.min(u16::MAX as u32) as u16));
+    }
+
+    encoder.encode_image(&rgb)?;
+
+    Ok(bytes)
+}
+
+/// Composites the image over white, for formats that have no alpha channel.
+fn flatten(img: &RgbaImage) -> RgbaImage {
+    let mut flat = img.clone();
+
+    for pixel in flat.pixels_mut() {
+        let alpha = pixel[3] as u16;
+
+        for channel in 0..3 {
+            pixel[channel] = ((pixel[channel] as u16 * alpha + 255 * (255 - alpha)) / 255) as u8;
         }
+
+        pixel[3] = 255;
     }
 
-    img.save(file_name).unwrap();
+    flat
+}
+
+fn is_dark(pixel: &Rgba<u8>) -> bool {
+    colour::reflectance(*pixel) < 0.5
+}
+
+/// Packs pixels into rows of bits, most significant bit first, each row padded to a whole byte.
+fn pack_bits(img: &RgbaImage, is_set: impl Fn(&Rgba<u8>) -> bool) -> Vec<u8> {
+    let row_bytes = img.width().div_ceil(8) as usize;
+    let mut data = vec![0u8; row_bytes * img.height() as usize];
+
+    for (x, y, pixel) in img.enumerate_pixels() {
+        if is_set(pixel) {
+            data[y as usize * row_bytes + x as usize / 8] |= 0x80 >> (x % 8);
+        }
+    }
+
+    data
+}
+
+fn to_image_error(err: impl std::error::Error + Send + Sync + 'static) -> ImageError {
+    ImageError::IoError(std::io::Error::other(err))
 }
 
 fn put_module(
-    img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
-    module_size: u32,
-    module_value: u8,
+    img: &mut RgbaImage,
+    options: &ImageOptions,
+    layout: &Layout,
+    matrix: &CodeMatrix,
     x: usize,
     y: usize,
 ) {
+    let module_size = options.module_size;
+    let offset_x = layout.symbol_x + options.quiet_zone * module_size;
+    let offset_y = options.quiet_zone * module_size;
+
+    // Raster output can only take whole pixels off
+    let outline = print_gain::module_outline(matrix, y, x, options.print_gain.round());
+
     for i in 0..module_size {
         for j in 0..module_size {
+            if !outline.contains(j as f64 + 0.5, i as f64 + 0.5, module_size as f64) {
+                continue;
+            }
+
             img.put_pixel(
-                (QUIET_ZONE as u32 * module_size) + x as u32 * module_size + j,
-                (QUIET_ZONE as u32 * module_size) + y as u32 * module_size + i,
-                if module_value == 1 {
-                    Rgb([0, 0, 0])
-                } else {
-                    Rgb([255, 255, 255])
-                },
+                offset_x + x as u32 * module_size + j,
+                offset_y + y as u32 * module_size + i,
+                options.foreground,
             )
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finder_matrix() -> CodeMatrix {
+        let mut matrix = CodeMatrix::new();
+        matrix.write(0, 0, 1);
+        matrix
+    }
+
+    #[test]
+    fn test_render_canvas_includes_quiet_zone() {
+        let options = ImageOptions {
+            quiet_zone: 4,
+            ..ImageOptions::new(3)
+        };
+
+        let img = render(&options, &finder_matrix());
+
+        assert_eq!(img.width(), (17 + 2 * 4) * 3);
+        assert_eq!(img.height(), (17 + 2 * 4) * 3);
+        assert_eq!(*img.get_pixel(11, 11), options.background);
+        assert_eq!(*img.get_pixel(12, 12), options.foreground);
+        assert_eq!(*img.get_pixel(15, 15), options.background);
+    }
+
+    #[test]
+    fn test_render_reversed_reflectance() {
+        let options = ImageOptions {
+            quiet_zone: 0,
+            ..ImageOptions::new(1)
+        }
+        .with_reversed_reflectance();
+
+        let img = render(&options, &finder_matrix());
+
+        assert_eq!(*img.get_pixel(0, 0), colour::WHITE);
+        assert_eq!(*img.get_pixel(1, 0), colour::BLACK);
+    }
+
+    #[test]
+    fn test_render_print_gain_keeps_module_pitch() {
+        let mut matrix = CodeMatrix::new();
+        matrix.write(0, 0, 1);
+        matrix.write(0, 1, 1);
+
+        let options = ImageOptions {
+            quiet_zone: 0,
+            print_gain: 2.0,
+            ..ImageOptions::new(4)
+        };
+
+        let img = render(&options, &matrix);
+        let row: Vec<bool> = (0..9)
+            .map(|x| *img.get_pixel(x, 2) == options.foreground)
+            .collect();
+
+        // Two joined modules lose one pixel at each outer edge but none at the join
+        assert_eq!(
+            row,
+            [false, true, true, true, true, true, true, false, false]
+        );
+        assert_eq!(*img.get_pixel(2, 0), options.background);
+        assert_eq!(*img.get_pixel(2, 3), options.background);
+    }
+
+    #[test]
+    fn test_pack_bits_pads_rows() {
+        let options = ImageOptions {
+            quiet_zone: 0,
+            ..ImageOptions::new(1)
+        };
+
+        let img = render(&options, &finder_matrix());
+        let data = pack_bits(&img, is_dark);
+
+        // 17 pixels per row packs into 3 bytes
+        assert_eq!(data.len(), 3 * 17);
+        assert_eq!(&data[0..3], &[0b1000_0000, 0, 0]);
+        assert_eq!(&data[3..6], &[0, 0, 0]);
+    }
+
+    #[test]
+    fn test_encode_pbm_header() {
+        let options = ImageOptions::new(2);
+        let pbm = encode(OutputFormat::Pbm, &options, &finder_matrix()).unwrap();
+
+        assert!(pbm.starts_with(b"P4\n42 42\n"));
+        assert_eq!(pbm.len(), "P4\n42 42\n".len() + 6 * 42);
+    }
+
+    #[test]
+    fn test_encode_round_trips_through_decoder() {
+        let options = ImageOptions::new(2);
+
+        for format in [
+            OutputFormat::Png,
+            OutputFormat::MonoPng,
+            OutputFormat::Pgm,
+            OutputFormat::Bmp,
+            OutputFormat::Tiff,
+            OutputFormat::Gif,
+            OutputFormat::Webp,
+        ] {
+            let bytes = encode(format, &options, &finder_matrix()).unwrap();
+            let decoded = image::load_from_memory(&bytes).unwrap().to_luma8();
+
+            assert_eq!(decoded.dimensions(), (42, 42), "{:?}", format);
+            assert_eq!(decoded.get_pixel(4, 4)[0], 0, "{:?}", format);
+            assert_eq!(decoded.get_pixel(6, 4)[0], 255, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_encode_writes_resolution() {
+        let options = ImageOptions {
+            dpi: Some(300),
+            ..ImageOptions::new(2)
+        };
+        let ppm = print_size::dots_per_metre(300);
+
+        let png = encode(OutputFormat::Png, &options, &finder_matrix()).unwrap();
+        let reader = png::Decoder::new(Cursor::new(png)).read_info().unwrap();
+        let dims = reader.info().pixel_dims.unwrap();
+        assert_eq!((dims.xppu, dims.yppu), (ppm, ppm));
+        assert_eq!(dims.unit, png::Unit::Meter);
+
+        let tiff = encode(OutputFormat::Tiff, &options, &finder_matrix()).unwrap();
+        let mut decoder = tiff::decoder::Decoder::new(Cursor::new(tiff)).unwrap();
+        for tag in [tiff::tags::Tag::XResolution, tiff::tags::Tag::YResolution] {
+            assert_eq!(
+                decoder.get_tag(tag).unwrap(),
+                tiff::decoder::ifd::Value::Rational(300, 1)
+            );
+        }
+        assert_eq!(
+            decoder
+                .get_tag_u32(tiff::tags::Tag::ResolutionUnit)
+                .unwrap(),
+            tiff::tags::ResolutionUnit::Inch.to_u16() as u32
+        );
+
+        let bmp = encode(OutputFormat::Bmp, &options, &finder_matrix()).unwrap();
+        let field = |offset: usize| u32::from_le_bytes(bmp[offset..offset + 4].try_into().unwrap());
+        assert_eq!(field(BMP_RESOLUTION_OFFSET), ppm);
+        assert_eq!(field(BMP_RESOLUTION_OFFSET + 4), ppm);
+    }
+
+    #[test]
+    fn test_encode_rejects_vector_formats() {
+        assert!(encode(OutputFormat::Svg, &ImageOptions::new(1), &finder_matrix()).is_err());
+    }
+
+    #[test]
+    fn test_layout_with_hri() {
+        let options = ImageOptions {
+            hri: Some(Hri {
+                text: String::from("ABCDEFGHIJ"),
+                font_size: 14,
+                spacing: 4,
+            }),
+            ..ImageOptions::new(2)
+        };
+
+        // Text is 10 glyphs * 6 - 1 columns at scale 2, wider than the 42 pixel symbol
+        assert_eq!(
+            options.layout(),
+            Layout {
+                width: 126,
+                height: 42 + 4 + 14 + 4,
+                symbol_x: 42,
+                symbol_size: 42,
+                text_y: 46,
+                text_height: 14,
+            }
+        );
+    }
+
+    #[test]
+    fn test_render_hri_below_quiet_zone() {
+        let options = ImageOptions {
+            hri: Some(Hri {
+                text: String::from("-"),
+                font_size: 7,
+                spacing: 2,
+            }),
+            ..ImageOptions::new(1)
+        };
+
+        let img = render(&options, &finder_matrix());
+
+        assert_eq!(img.dimensions(), (21, 21 + 2 + 7 + 2));
+
+        // The dash is the middle row of a glyph centred under the symbol
+        assert_eq!(*img.get_pixel(8, 23 + 3), options.foreground);
+        assert_eq!(*img.get_pixel(12, 23 + 3), options.foreground);
+        assert_eq!(*img.get_pixel(13, 23 + 3), options.background);
+        assert_eq!(*img.get_pixel(10, 23 + 2), options.background);
+    }
+}
@@ -1,10 +1,27 @@
 use crate::code_matrix::CodeMatrix;
 use crate::data_mask::DataMask;
-use crate::format_info;
+use crate::{alphanumeric_mode, data_codewords, format_info};
 
-pub fn generate(data_codewords: &[u8]) -> CodeMatrix {
-    let data_matrix = CodeMatrix::with_data(&data_codewords);
-    let data_mask = DataMask::best_pattern(&data_matrix);
+/// Validates an input string and builds its symbol, with the given mask pattern reference (0-3)
+/// or the best scoring mask when `None`.
+pub fn encode(input: &str, pattern_ref: Option<u8>) -> Result<CodeMatrix, String> {
+    let data = alphanumeric_mode::validate_input(input)?;
+
+    let data_mask = match pattern_ref {
+        Some(pattern_ref) => Some(
+            DataMask::from_pattern_ref(pattern_ref)
+                .ok_or_else(|| format!("Mask must be between 0 and 3, got {}", pattern_ref))?,
+        ),
+        None => None,
+    };
+
+    Ok(generate(&data_codewords::generate(&data), data_mask))
+}
+
+/// Builds the final symbol, using the given data mask or the best scoring one when `None`.
+pub fn generate(data_codewords: &[u8], data_mask: Option<DataMask>) -> CodeMatrix {
+    let data_matrix = CodeMatrix::with_data(data_codewords);
+    let data_mask = data_mask.unwrap_or_else(|| DataMask::best_pattern(&data_matrix));
 
     let masked_matrix = data_matrix.with_data_mask(&data_mask);
     let format_info = format_info::encode(data_mask.pattern_ref());
@@ -0,0 +1,51 @@
+use crate::code_matrix::CodeMatrix;
+use crate::output_format::OutputFormat;
+use crate::symbol_image::ImageOptions;
+use crate::{label_printer, matrix_text, symbol_data, symbol_image, symbol_pdf, symbol_svg};
+use image::ImageError;
+use std::io::Write;
+
+/// Output destination meaning standard output.
+pub const STDOUT: &str = "-";
+
+/// Renders the symbol in any supported output format.
+pub fn encode(
+    format: OutputFormat,
+    options: &ImageOptions,
+    matrix: &CodeMatrix,
+) -> Result<Vec<u8>, ImageError> {
+    match format {
+        OutputFormat::Svg => Ok(symbol_svg::render(options, matrix).into_bytes()),
+        OutputFormat::Pdf => Ok(symbol_pdf::render(options, matrix)),
+        OutputFormat::Zpl => Ok(label_printer::zpl(options, matrix)),
+        OutputFormat::Epl => Ok(label_printer::epl(options, matrix)),
+        OutputFormat::Escpos => Ok(label_printer::escpos(options, matrix)),
+        OutputFormat::Text => Ok(matrix_text::render(matrix).into_bytes()),
+        OutputFormat::Json => Ok(symbol_data::json(matrix).into_bytes()),
+        OutputFormat::Csv => Ok(symbol_data::csv(matrix)),
+        _ => symbol_image::encode(format, options, matrix),
+    }
+}
+
+/// Writes the encoded symbol to a file, or to standard output when the destination is `-`.
+pub fn write(
+    destination: &str,
+    format: OutputFormat,
+    options: &ImageOptions,
+    matrix: &CodeMatrix,
+) -> Result<(), ImageError> {
+    write_bytes(destination, &encode(format, options, matrix)?)
+}
+
+/// Writes already encoded output to a file, or to standard output when the destination is `-`.
+pub fn write_bytes(destination: &str, bytes: &[u8]) -> Result<(), ImageError> {
+    if destination == STDOUT {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(bytes)?;
+        stdout.flush()?;
+    } else {
+        std::fs::write(destination, bytes)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,306 @@
+use crate::code_matrix::CodeMatrix;
+use crate::print_gain;
+use crate::symbol_image::ImageOptions;
+use crate::symbol_svg::number;
+use image::Rgba;
+use std::fmt::Write;
+
+const POINTS_PER_INCH: f64 = 72.0;
+
+/// Height of capital letters in Courier as a fraction of the font size.
+pub const CAP_HEIGHT: f64 = 0.7;
+
+// Every Courier glyph advances by 600/1000 of the font size
+const COURIER_ADVANCE: f64 = 0.6;
+
+pub fn render(options: &ImageOptions, matrix: &CodeMatrix) -> Vec<u8> {
+    let layout = options.layout();
+
+    // Without a printer resolution one pixel maps to one point
+    let scale = POINTS_PER_INCH / options.dpi.unwrap_or(72) as f64;
+    let (page_width, page_height) = (layout.width as f64 * scale, layout.height as f64 * scale);
+
+    let mut content = String::new();
+
+    // Flip the y axis so the symbol is drawn top-down in pixel units, as in the other renderers
+    writeln!(
+        content,
+        "{} 0 0 {} 0 {} cm",
+        number(scale),
+        number(-scale),
+        number(page_height)
+    )
+    .unwrap();
+
+    content.push_str(&symbol_content(options, matrix));
+
+    document(
+        page_width,
+        page_height,
+        &[content],
+        &[options.foreground, options.background],
+    )
+}
+
+/// Operators drawing the background, modules and text of the symbol, in pixel units from the
+/// top-left of its layout with the y axis pointing down.
+pub fn symbol_content(options: &ImageOptions, matrix: &CodeMatrix) -> String {
+    let layout = options.layout();
+    let mut symbol = String::new();
+
+    // Saving the graphics state keeps a translucent background's opacity off the modules
+    if options.background[3] > 0 {
+        writeln!(symbol, "q {}", fill(options.background)).unwrap();
+        writeln!(symbol, "0 0 {} {} re f Q", layout.width, layout.height).unwrap();
+    }
+
+    symbol.push_str(&module_paths(options, matrix));
+
+    if let Some(hri) = &options.hri {
+        let font_size = layout.text_height as f64 / CAP_HEIGHT;
+        let text_width = hri.text.chars().count() as f64 * font_size * COURIER_ADVANCE;
+
+        // The text matrix flips y back so the glyphs stand upright
+        writeln!(
+            symbol,
+            "BT /F1 {} Tf 1 0 0 -1 {} {} Tm ({}) Tj ET",
+            number(font_size),
+            number((layout.width as f64 - text_width) / 2.0),
+            layout.text_y + layout.text_height,
+            escape(&hri.text)
+        )
+        .unwrap();
+    }
+
+    symbol
+}
+
+/// Drawing operators that fill every dark module, in pixel units from the top-left of the quiet zone.
+pub fn module_paths(options: &ImageOptions, matrix: &CodeMatrix) -> String {
+    let module_size = options.module_size as f64;
+    let offset_x = (options.layout().symbol_x + options.quiet_zone * options.module_size) as f64;
+    let offset_y = (options.quiet_zone * options.module_size) as f64;
+
+    let mut paths = String::new();
+
+    writeln!(paths, "{}", fill(options.foreground)).unwrap();
+
+    for (y, x, outline) in print_gain::dark_module_outlines(matrix, options.print_gain) {
+        let (origin_x, origin_y) = (
+            offset_x + x as f64 * module_size,
+            offset_y + y as f64 * module_size,
+        );
+
+        for (i, (px, py)) in outline.polygon(module_size).into_iter().enumerate() {
+            let operator = if i == 0 { 'm' } else { 'l' };
+            writeln!(
+                paths,
+                "{} {} {}",
+                number(origin_x + px),
+                number(origin_y + py),
+                operator
+            )
+            .unwrap();
+        }
+
+        paths.push_str("h\n");
+    }
+
+    paths.push_str("f\n");
+
+    paths
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('(', "\\(")
+        .replace(')', "\\)")
+}
+
+/// Non-stroking colour operator for an RGB colour, and for a translucent one the graphics state
+/// `document` declares for its opacity, which stays in effect until the state is restored.
+pub fn fill(colour: Rgba<u8>) -> String {
+    let Rgba([r, g, b, a]) = colour;
+    let mut operators = format!(
+        "{} {} {} rg",
+        number(r as f64 / 255.0),
+        number(g as f64 / 255.0),
+        number(b as f64 / 255.0)
+    );
+
+    if a < 255 {
+        write!(operators, " /A{} gs", a).unwrap();
+    }
+
+    operators
+}
+
+/// Graphics state resources setting the fill opacity of each translucent colour, as `fill` names them.
+fn opacity_states(colours: &[Rgba<u8>]) -> String {
+    let mut alphas: Vec<u8> = colours
+        .iter()
+        .map(|colour| colour[3])
+        .filter(|&a| a < 255)
+        .collect();
+    alphas.sort_unstable();
+    alphas.dedup();
+
+    if alphas.is_empty() {
+        return String::new();
+    }
+
+    let states: Vec<String> = alphas
+        .iter()
+        .map(|&a| format!("/A{} << /ca {} >>", a, number(a as f64 / 255.0)))
+        .collect();
+
+    format!(" /ExtGState << {} >>", states.join(" "))
+}
+
+/// Assembles a PDF document with one page per content stream, every page the same size in points,
+/// declaring the opacity of any translucent colours the pages fill with.
+pub fn document(width: f64, height: f64, pages: &[String], colours: &[Rgba<u8>]) -> Vec<u8> {
+    let kids: Vec<String> = (0..pages.len())
+        .map(|i| format!("{} 0 R", 3 + 2 * i))
+        .collect();
+
+    let mut objects = vec![
+        String::from("<< /Type /Catalog /Pages 2 0 R >>"),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        ),
+    ];
+
+    for (i, content) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Contents {} 0 R \
+             /Resources << /Font << /F1 << /Type /Font /Subtype /Type1 /BaseFont /Courier >> >>{} >> >>",
+            number(width),
+            number(height),
+            4 + 2 * i,
+            opacity_states(colours)
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            content.len(),
+            content
+        ));
+    }
+
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        write!(pdf, "{} 0 obj\n{}\nendobj\n", i + 1, object).unwrap();
+    }
+
+    let xref_offset = pdf.len();
+
+    write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).unwrap();
+
+    for offset in offsets {
+        writeln!(pdf, "{:010} 00000 n ", offset).unwrap();
+    }
+
+    write!(
+        pdf,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref_offset
+    )
+    .unwrap();
+
+    pdf.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbol_image::Hri;
+
+    #[test]
+    fn test_render_page_size() {
+        let options = ImageOptions {
+            dpi: Some(144),
+            ..ImageOptions::new(10)
+        };
+
+        let pdf = String::from_utf8(render(&options, &CodeMatrix::new())).unwrap();
+
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.contains("/MediaBox [0 0 105 105]"));
+        assert!(pdf.contains("0.5 0 0 -0.5 0 105 cm"));
+        assert!(pdf.ends_with("%%EOF\n"));
+    }
+
+    #[test]
+    fn test_xref_offsets_point_at_objects() {
+        let pdf = String::from_utf8(document(10.0, 10.0, &[String::new()], &[])).unwrap();
+        let xref = pdf.find("xref\n").unwrap();
+
+        for (i, line) in pdf[xref..].lines().skip(3).take(4).enumerate() {
+            let offset: usize = line[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(&format!("{} 0 obj", i + 1)));
+        }
+    }
+
+    #[test]
+    fn test_module_paths() {
+        let mut matrix = CodeMatrix::new();
+        matrix.write(0, 0, 1);
+
+        let paths = module_paths(&ImageOptions::new(10), &matrix);
+
+        assert_eq!(
+            paths,
+            "0 0 0 rg\n20 20 m\n30 20 l\n30 30 l\n20 30 l\nh\nf\n"
+        );
+    }
+
+    #[test]
+    fn test_render_hri_text() {
+        let options = ImageOptions {
+            hri: Some(Hri {
+                text: String::from("AB"),
+                font_size: 14,
+                spacing: 10,
+            }),
+            ..ImageOptions::new(10)
+        };
+
+        let pdf = String::from_utf8(render(&options, &CodeMatrix::new())).unwrap();
+
+        assert!(pdf.contains("/MediaBox [0 0 210 244]"));
+        assert!(pdf.contains("/BaseFont /Courier"));
+        assert!(pdf.contains("BT /F1 20 Tf 1 0 0 -1 93 234 Tm (AB) Tj ET"));
+    }
+
+    #[test]
+    fn test_render_translucent_colours() {
+        let options = ImageOptions {
+            foreground: Rgba([0, 0, 0, 128]),
+            background: Rgba([255, 255, 255, 51]),
+            ..ImageOptions::new(10)
+        };
+
+        let pdf = String::from_utf8(render(&options, &CodeMatrix::new())).unwrap();
+
+        assert!(pdf.contains("/ExtGState << /A51 << /ca 0.2 >> /A128 << /ca 0.502 >> >>"));
+        assert!(pdf.contains("q 1 1 1 rg /A51 gs\n0 0 210 210 re f Q\n"));
+        assert!(pdf.contains("0 0 0 rg /A128 gs\n"));
+
+        // Opaque colours leave the page resources as they were
+        let pdf = String::from_utf8(render(&ImageOptions::new(10), &CodeMatrix::new())).unwrap();
+
+        assert!(!pdf.contains("ExtGState"));
+        assert!(!pdf.contains(" gs"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("A(B)\\"), "A\\(B\\)\\\\");
+    }
+}
@@ -0,0 +1,837 @@
+use crate::binarise::{Binariser, Method};
+use crate::code_matrix::CodeMatrix;
+use crate::colour;
+use crate::decoder::{self, Decoded};
+use crate::finder_pattern::{self, Components, Finder};
+use crate::format_info;
+use crate::perspective::{self, Homography, Point};
+use image::RgbaImage;
+use serde::Serialize;
+
+const MODULE_COUNT: usize = 17;
+
+// Finder pattern modules that may be misread before the image is taken not to hold a symbol
+const MAX_FINDER_ERRORS: usize = 4;
+
+// Outer corners of the finder pattern in module coordinates, clockwise from the symbol's corner
+const FINDER_OUTLINE: [Point; 4] = [(0.0, 0.0), (7.0, 0.0), (7.0, 7.0), (0.0, 7.0)];
+
+// Steps in modules when searching for a timing pattern edge
+const EDGE_SEARCH_STEP: f64 = 0.05;
+
+// Offsets in modules from a module centre of the points averaged to read it
+const SAMPLE_POINTS: [Point; 5] = [(0.0, 0.0), (-0.2, 0.0), (0.2, 0.0), (0.0, -0.2), (0.0, 0.2)];
+
+/// Module grid of a symbol found in an image, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grid {
+    pub origin_x: f64,
+    pub origin_y: f64,
+    pub pitch_x: f64,
+    pub pitch_y: f64,
+    /// Light modules on a dark background
+    pub reversed: bool,
+}
+
+impl Grid {
+    /// Pixel position of the centre of the module at row `y`, column `x`.
+    pub fn centre(&self, y: usize, x: usize) -> (f64, f64) {
+        (
+            self.origin_x + (x as f64 + 0.5) * self.pitch_x,
+            self.origin_y + (y as f64 + 0.5) * self.pitch_y,
+        )
+    }
+}
+
+/// Finds and decodes a symbol in an image, trying reversed reflectance if the normal reading fails.
+/// Upright symbols are read from their grid, and anything else through a perspective transform,
+/// first with a global threshold and then with local ones for shadowed or glossy labels.
+pub fn decode_image(img: &RgbaImage) -> Result<Decoded, String> {
+    let local = Binariser::new(Method::Sauvola, img.width(), img.height());
+
+    read(img)
+        .map(|(decoded, _)| decoded)
+        .or_else(|_| read_perspective(img, &Binariser::GLOBAL).map(|(decoded, _)| decoded))
+        .or_else(|_| read_perspective(img, &local).map(|(decoded, _)| decoded))
+}
+
+/// Decodes an upright symbol along with the grid it was sampled on.
+pub fn read(img: &RgbaImage) -> Result<(Decoded, Grid), String> {
+    let attempt = |reversed| {
+        let grid = locate(img, reversed)?;
+        let decoded = decoder::decode(&sample(img, &grid)?)?;
+
+        Ok((decoded, grid))
+    };
+
+    attempt(false).or_else(|err: String| attempt(true).map_err(|_| err))
+}
+
+/// Decodes a symbol at any rotation or tilt, along with the transform from module coordinates to
+/// the image that it was sampled through.
+pub fn read_perspective(
+    img: &RgbaImage,
+    binariser: &Binariser,
+) -> Result<(Decoded, Homography), String> {
+    let reflectance = reflectance_map(img);
+    let thresholds = binariser.thresholds(&reflectance, img.width(), img.height())?;
+
+    let attempt = |reversed| {
+        let darkness = Darkness::new(
+            &reflectance,
+            &thresholds,
+            img.width(),
+            img.height(),
+            reversed,
+        );
+        let components = Components::label(&darkness.dark_pixels(), img.width(), img.height());
+        let mut error = String::from("No symbol found: finder pattern not recognised");
+
+        for finder in finder_pattern::find(&components) {
+            let result = locate_perspective(&darkness, &finder).and_then(|(homography, _)| {
+                let decoded = decoder::decode(&sample_perspective(&darkness, &homography)?)?;
+
+                Ok((decoded, homography))
+            });
+
+            match result {
+                Ok(read) => return Ok(read),
+                Err(err) => error = err,
+            }
+        }
+
+        Err(error)
+    };
+
+    attempt(false).or_else(|err: String| attempt(true).map_err(|_| err))
+}
+
+/// A symbol found among others in an image, with its payload or why it could not be read.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Found {
+    /// Symbol version and error correction level, when the format information can be read
+    pub version: Option<&'static str>,
+    /// Top left, top right, bottom right and bottom left corners of the symbol, without its quiet
+    /// zone, in the image
+    pub corners: [Point; 4],
+    pub centre: Point,
+    /// Clockwise rotation of the symbol's top edge from the image's x axis, in degrees from -180 to
+    /// 180
+    pub orientation: f64,
+    /// Whether the symbol is a mirror image
+    pub mirrored: bool,
+    pub text: Option<String>,
+    pub error: Option<String>,
+}
+
+impl Found {
+    /// Whether a point lies within half a symbol side of the centre.
+    fn contains(&self, point: Point) -> bool {
+        let [a, b, ..] = self.corners;
+        let side = (b.0 - a.0).hypot(b.1 - a.1);
+
+        (point.0 - self.centre.0).hypot(point.1 - self.centre.1) < side / 2.0
+    }
+}
+
+/// Finds every symbol in an image, of any version, rotation or tilt, top to bottom.
+///
+/// Every finder pattern, by the global threshold and by local ones in either polarity, is taken
+/// as a candidate symbol. Candidates whose centres lie within half a symbol of each other are the
+/// same symbol, read more than once, and the first reading that decodes is kept.
+pub fn read_all(img: &RgbaImage) -> Result<Vec<Found>, String> {
+    let reflectance = reflectance_map(img);
+    let local = Binariser::new(Method::Sauvola, img.width(), img.height());
+    let mut found: Vec<Found> = Vec::new();
+
+    for binariser in [Binariser::GLOBAL, local] {
+        let thresholds = binariser.thresholds(&reflectance, img.width(), img.height())?;
+
+        for reversed in [false, true] {
+            let darkness = Darkness::new(
+                &reflectance,
+                &thresholds,
+                img.width(),
+                img.height(),
+                reversed,
+            );
+            let components = Components::label(&darkness.dark_pixels(), img.width(), img.height());
+
+            for finder in finder_pattern::find(&components) {
+                let Some(candidate) = read_candidate(&darkness, &finder) else {
+                    continue;
+                };
+
+                match found
+                    .iter_mut()
+                    .find(|other| other.contains(candidate.centre))
+                {
+                    Some(other) if other.text.is_none() && candidate.text.is_some() => {
+                        *other = candidate
+                    }
+                    Some(_) => {}
+                    None => found.push(candidate),
+                }
+            }
+        }
+    }
+
+    found.sort_by(|a, b| {
+        let ((ax, ay), (bx, by)) = (a.centre, b.centre);
+        ay.total_cmp(&by).then(ax.total_cmp(&bx))
+    });
+
+    Ok(found)
+}
+
+/// The symbol a finder pattern belongs to, or `None` if it is not part of one.
+fn read_candidate(darkness: &Darkness, finder: &Finder) -> Option<Found> {
+    let (homography, size) = locate_perspective(darkness, finder).ok()?;
+    let (matrix, mirrored) = decoder::unmirror(&sample_perspective(darkness, &homography).ok()?);
+    let side = size as f64;
+
+    let corners =
+        [(0.0, 0.0), (side, 0.0), (side, side), (0.0, side)].map(|corner| homography.map(corner));
+    let (dx, dy) = (corners[1].0 - corners[0].0, corners[1].1 - corners[0].1);
+    let version = format_info::decode(matrix.read_format_info())
+        .map(|(symbol_number, _)| format_info::SYMBOL_NAMES[symbol_number as usize]);
+    let (text, error) = match decoder::decode_any(&matrix) {
+        Ok(decoded) => (Some(decoded.text), None),
+        Err(err) => (None, Some(err)),
+    };
+
+    Some(Found {
+        version,
+        corners,
+        centre: homography.map((side / 2.0, side / 2.0)),
+        orientation: dy.atan2(dx).to_degrees(),
+        mirrored,
+        text,
+        error,
+    })
+}
+
+/// Reflectance of every pixel, row by row.
+pub fn reflectance_map(img: &RgbaImage) -> Vec<f64> {
+    img.pixels().map(|p| colour::reflectance(*p)).collect()
+}
+
+/// Lowest and highest reflectance in the image.
+pub fn reflectance_range(reflectance: &[f64]) -> (f64, f64) {
+    let min = reflectance.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = reflectance
+        .iter()
+        .cloned()
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    (min, max)
+}
+
+/// Locates the module grid of an upright, unskewed symbol, like those this tool renders.
+///
+/// The grid is found from the timing patterns: the first and last dark runs along the top row
+/// and the left column are the finder pattern edge and the last timing module. Their centres are
+/// unaffected by print gain, unlike the outer edges of the symbol.
+pub fn locate(img: &RgbaImage, reversed: bool) -> Result<Grid, String> {
+    let (width, height) = img.dimensions();
+    let dark = dark_pixels(img, reversed)?;
+    let dark = |x: u32, y: u32| dark[(y * width + x) as usize];
+
+    let top = (0..height)
+        .find(|&y| (0..width).any(|x| dark(x, y)))
+        .ok_or("No symbol found")?;
+
+    let columns = runs((0..width).map(|x| dark(x, top)));
+    let (pitch_x, origin_x) = grid_axis(&columns)?;
+
+    // Scan down the middle of the left column, stopping short of any text under the symbol
+    let column_x = (origin_x + pitch_x / 2.0) as u32;
+    let limit = ((top as f64 + pitch_x * (MODULE_COUNT as f64 + 0.5)) as u32).min(height);
+    let rows = runs((top..limit).map(|y| dark(column_x, y)));
+    let (pitch_y, origin_y) = grid_axis(&rows)?;
+
+    Ok(Grid {
+        origin_x,
+        origin_y: origin_y + top as f64,
+        pitch_x,
+        pitch_y,
+        reversed,
+    })
+}
+
+/// Reads the module at the centre of every grid cell, checking the finder pattern is there.
+pub fn sample(img: &RgbaImage, grid: &Grid) -> Result<CodeMatrix, String> {
+    let (width, height) = img.dimensions();
+    let dark = dark_pixels(img, grid.reversed)?;
+    let mut matrix = CodeMatrix::new();
+
+    for i in 0..MODULE_COUNT {
+        for j in 0..MODULE_COUNT {
+            let (x, y) = grid.centre(i, j);
+
+            if x < width as f64 && y < height as f64 && dark[(y as u32 * width + x as u32) as usize]
+            {
+                matrix.write(i, j, 1);
+            }
+        }
+    }
+
+    check_finder(&matrix)?;
+
+    Ok(matrix)
+}
+
+/// Transform from module coordinates to the image, fitted to the edges of the finder and timing
+/// patterns, and the number of modules along each side of the symbol.
+///
+/// The finder pattern corners give a first estimate. Each edge is then searched for across the line
+/// where that estimate puts it, found to a fraction of a pixel where the reflectance crosses the
+/// threshold, and added to the fit: the finder pattern edges first, then the timing pattern edges
+/// moving away from it, so the estimate improves before it is stretched to the far side. The
+/// timing patterns are followed as far as the smallest symbol's before the format information is
+/// read for the symbol's size, and then to its end.
+pub fn locate_perspective(
+    darkness: &Darkness,
+    finder: &Finder,
+) -> Result<(Homography, usize), String> {
+    // The ring corner at the symbol's corner is the one that lines the timing patterns up
+    let mut homography = (0..4)
+        .filter_map(|turn| {
+            let mut corners = finder.corners;
+            corners.rotate_left(turn);
+
+            Homography::from_quad(FINDER_OUTLINE, corners)
+        })
+        .max_by_key(|homography| timing_matches(darkness, homography))
+        .ok_or("No symbol found: finder pattern not recognised")?;
+
+    let mut pairs = vec![((3.5, 3.5), finder.centre)];
+    let mut timing_edges = 0;
+    let mut size = format_info::SYMBOL_SIZES[0];
+    let mut stage = 7;
+
+    while stage <= size {
+        let edges = match stage {
+            7 => finder_edges(),
+            k => timing_pattern_edges(k),
+        };
+
+        for (expected, across, entering_dark) in edges {
+            if let Some(pair) = find_edge(darkness, &homography, expected, across, entering_dark) {
+                pairs.push(pair);
+                timing_edges += (stage > 7) as usize;
+            }
+        }
+
+        homography =
+            Homography::fit(&pairs).ok_or("No symbol found: the symbol is too distorted")?;
+
+        if stage == format_info::SYMBOL_SIZES[0] {
+            // At least half of the smallest symbol's timing pattern edges, leaving a margin for damage
+            if timing_edges
+                < (8..=stage)
+                    .map(|k| timing_pattern_edges(k).len())
+                    .sum::<usize>()
+                    / 2
+            {
+                return Err(String::from(
+                    "No symbol found: timing pattern not recognised",
+                ));
+            }
+
+            // Symbols whose format information cannot be read are taken to be the largest
+            let (matrix, _) = decoder::unmirror(&sample_modules(darkness, &homography));
+            size = format_info::decode(matrix.read_format_info())
+                .map_or(MODULE_COUNT, |(symbol_number, _)| {
+                    format_info::SYMBOL_SIZES[symbol_number as usize]
+                });
+        }
+
+        stage += 1;
+    }
+
+    Ok((homography, size))
+}
+
+/// An edge in module coordinates, the direction it is crossed in, and whether it is crossed from
+/// light to dark.
+type Edge = (Point, Point, bool);
+
+/// The outer edges of the finder pattern, against the quiet zone, and its inner edges, against
+/// the separator, each at the middle of every module along them.
+fn finder_edges() -> Vec<Edge> {
+    (0..7)
+        .flat_map(|i| {
+            let middle = i as f64 + 0.5;
+
+            [
+                ((middle, 0.0), (0.0, 1.0), true),
+                ((0.0, middle), (1.0, 0.0), true),
+                ((middle, 7.0), (0.0, 1.0), false),
+                ((7.0, middle), (1.0, 0.0), false),
+            ]
+        })
+        .collect()
+}
+
+/// The timing pattern edges `k` modules from the symbol's corner: between modules along the top
+/// row and down the left column, and against the quiet zone when module `k` is dark.
+fn timing_pattern_edges(k: usize) -> Vec<Edge> {
+    // Even modules are dark, so the edge before one goes from light to dark
+    let entering_dark = k.is_multiple_of(2);
+    let (edge, middle) = (k as f64, k as f64 + 0.5);
+
+    let mut edges = vec![
+        ((edge, 0.5), (1.0, 0.0), entering_dark),
+        ((0.5, edge), (0.0, 1.0), entering_dark),
+    ];
+
+    if entering_dark && k < MODULE_COUNT {
+        edges.push(((middle, 0.0), (0.0, 1.0), true));
+        edges.push(((0.0, middle), (1.0, 0.0), true));
+    }
+
+    edges
+}
+
+/// Timing pattern modules, outside the finder pattern, that are read as they should be.
+fn timing_matches(darkness: &Darkness, homography: &Homography) -> usize {
+    (8..MODULE_COUNT)
+        .flat_map(|k| {
+            let centre = k as f64 + 0.5;
+            [(centre, 0.5), (0.5, centre)].map(|point| (point, k.is_multiple_of(2)))
+        })
+        .filter(|&(point, dark)| darkness.is_dark(homography.map(point)) == Some(dark))
+        .count()
+}
+
+/// The image position of an edge, searched for up to half a module either side of where the
+/// transform puts it, paired with its position in module coordinates.
+fn find_edge(
+    darkness: &Darkness,
+    homography: &Homography,
+    expected: Point,
+    across: Point,
+    entering_dark: bool,
+) -> Option<(Point, Point)> {
+    let point = |t: f64| (expected.0 + t * across.0, expected.1 + t * across.1);
+    let steps = (1.0 / EDGE_SEARCH_STEP).round() as usize;
+
+    let samples: Vec<(f64, f64)> = (0..=steps)
+        .map(|i| i as f64 * EDGE_SEARCH_STEP - 0.5)
+        .map(|t| Some((t, darkness.at(homography.map(point(t)))?)))
+        .collect::<Option<_>>()?;
+
+    let edge = samples
+        .windows(2)
+        .filter(|pair| match entering_dark {
+            true => pair[0].1 <= 0.0 && pair[1].1 > 0.0,
+            false => pair[0].1 > 0.0 && pair[1].1 <= 0.0,
+        })
+        .map(|pair| {
+            let ((t0, d0), (_, d1)) = (pair[0], pair[1]);
+            t0 + EDGE_SEARCH_STEP * d0 / (d0 - d1)
+        })
+        .min_by(|a, b| a.abs().total_cmp(&b.abs()))?;
+
+    Some((expected, homography.map(point(edge))))
+}
+
+/// Reads every module through the transform, averaging a few points around its centre, checking
+/// the finder pattern is there.
+pub fn sample_perspective(
+    darkness: &Darkness,
+    homography: &Homography,
+) -> Result<CodeMatrix, String> {
+    let matrix = sample_modules(darkness, homography);
+    check_finder(&matrix)?;
+
+    Ok(matrix)
+}
+
+fn sample_modules(darkness: &Darkness, homography: &Homography) -> CodeMatrix {
+    let mut matrix = CodeMatrix::new();
+
+    for i in 0..MODULE_COUNT {
+        for j in 0..MODULE_COUNT {
+            let total: f64 = SAMPLE_POINTS
+                .iter()
+                .filter_map(|&(dx, dy)| {
+                    darkness.at(homography.map((j as f64 + 0.5 + dx, i as f64 + 0.5 + dy)))
+                })
+                .sum();
+
+            if total > 0.0 {
+                matrix.write(i, j, 1);
+            }
+        }
+    }
+
+    matrix
+}
+
+fn check_finder(matrix: &CodeMatrix) -> Result<(), String> {
+    let finder_errors = (0..7)
+        .flat_map(|i| (0..7).map(move |j| (i, j)))
+        .filter(|&(i, j)| matrix.read(i, j) != finder_module(i, j))
+        .count();
+
+    if finder_errors > MAX_FINDER_ERRORS {
+        return Err(String::from(
+            "No symbol found: finder pattern not recognised",
+        ));
+    }
+
+    Ok(())
+}
+
+/// How far each pixel's reflectance lies on the dark side of its threshold.
+pub struct Darkness<'a> {
+    reflectance: &'a [f64],
+    thresholds: &'a [f64],
+    width: u32,
+    height: u32,
+    reversed: bool,
+}
+
+impl<'a> Darkness<'a> {
+    pub fn new(
+        reflectance: &'a [f64],
+        thresholds: &'a [f64],
+        width: u32,
+        height: u32,
+        reversed: bool,
+    ) -> Self {
+        Self {
+            reflectance,
+            thresholds,
+            width,
+            height,
+            reversed,
+        }
+    }
+
+    /// Positive for dark, between pixel centres, or `None` outside the image.
+    pub fn at(&self, point: Point) -> Option<f64> {
+        let reflectance =
+            perspective::interpolate(self.reflectance, self.width, self.height, point)?;
+        let threshold = perspective::interpolate(self.thresholds, self.width, self.height, point)?;
+
+        Some(match self.reversed {
+            true => reflectance - threshold,
+            false => threshold - reflectance,
+        })
+    }
+
+    pub fn is_dark(&self, point: Point) -> Option<bool> {
+        self.at(point).map(|darkness| darkness > 0.0)
+    }
+
+    pub fn dark_pixels(&self) -> Vec<bool> {
+        self.reflectance
+            .iter()
+            .zip(self.thresholds)
+            .map(|(r, threshold)| (r < threshold) != self.reversed)
+            .collect()
+    }
+}
+
+/// Dark pixels by the global threshold.
+fn dark_pixels(img: &RgbaImage, reversed: bool) -> Result<Vec<bool>, String> {
+    let reflectance = reflectance_map(img);
+    let thresholds = Binariser::GLOBAL.thresholds(&reflectance, img.width(), img.height())?;
+
+    Ok(Darkness::new(
+        &reflectance,
+        &thresholds,
+        img.width(),
+        img.height(),
+        reversed,
+    )
+    .dark_pixels())
+}
+
+/// Start and length of every dark run along a line of pixels.
+pub fn runs(line: impl Iterator<Item = bool>) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    let mut length = 0;
+
+    for (i, dark) in line.enumerate() {
+        match (dark, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                runs.push((s, i - s));
+                start = None;
+            }
+            _ => {}
+        }
+
+        length = i + 1;
+    }
+
+    if let Some(s) = start {
+        runs.push((s, length - s));
+    }
+
+    runs
+}
+
+/// Module pitch and grid origin along a timing pattern, from the centres of the 7 module finder
+/// edge and the final timing module 13 modules further on.
+fn grid_axis(runs: &[(usize, usize)]) -> Result<(f64, f64), String> {
+    let centre = |(start, length): (usize, usize)| start as f64 + length as f64 / 2.0;
+
+    let (first, last) = match (runs.first(), runs.last()) {
+        (Some(&first), Some(&last)) if runs.len() > 1 => (first, last),
+        _ => {
+            return Err(String::from(
+                "No symbol found: timing pattern not recognised",
+            ))
+        }
+    };
+
+    let pitch = (centre(last) - centre(first)) / 13.0;
+
+    if pitch < 1.0 {
+        return Err(String::from("No symbol found: modules are too small"));
+    }
+
+    Ok((pitch, centre(first) - 3.5 * pitch))
+}
+
+pub fn finder_module(i: usize, j: usize) -> u8 {
+    let ring = i.min(j).min(6 - i).min(6 - j);
+    (ring != 1) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_mask::DataMask;
+    use crate::degradation::{self, Degradation};
+    use crate::gf_256::GF256;
+    use crate::output_format::OutputFormat;
+    use crate::symbol_image::{self, Hri, ImageOptions};
+    use crate::{data_codewords, error_correction, symbol_matrix};
+    use image::buffer::ConvertBuffer;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn encode(text: &str) -> CodeMatrix {
+        symbol_matrix::generate(&data_codewords::generate(text), None)
+    }
+
+    #[test]
+    fn test_decode_rendered_symbol() {
+        let options = ImageOptions {
+            hri: Some(Hri {
+                text: String::from("HELLO WORLD"),
+                font_size: 14,
+                spacing: 5,
+            }),
+            ..ImageOptions::new(5)
+        };
+
+        let img = symbol_image::render(&options, &encode("HELLO WORLD"));
+
+        assert_eq!(decode_image(&img).unwrap().text, "HELLO WORLD");
+    }
+
+    #[test]
+    fn test_decode_with_print_gain_and_reversed_reflectance() {
+        let options = ImageOptions {
+            print_gain: 2.0,
+            quiet_zone: 4,
+            ..ImageOptions::new(7)
+        }
+        .with_reversed_reflectance();
+
+        let img = symbol_image::render(&options, &encode("REVERSED"));
+
+        assert_eq!(decode_image(&img).unwrap().text, "REVERSED");
+    }
+
+    #[test]
+    fn test_decode_encoded_file() {
+        let bytes =
+            symbol_image::encode(OutputFormat::Png, &ImageOptions::new(3), &encode("PNG")).unwrap();
+        let img = image::load_from_memory(&bytes).unwrap().to_rgba8();
+
+        assert_eq!(decode_image(&img).unwrap().text, "PNG");
+    }
+
+    #[test]
+    fn test_blank_image_has_no_symbol() {
+        let img = RgbaImage::from_pixel(50, 50, colour::WHITE);
+
+        assert!(decode_image(&img).is_err());
+    }
+
+    fn capture(text: &str, degradation: Degradation, seed: u64) -> RgbaImage {
+        let img = symbol_image::render(&ImageOptions::new(8), &encode(text));
+        let corners = [(16.0, 16.0), (152.0, 16.0), (152.0, 152.0), (16.0, 152.0)];
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        degradation::capture(&img, corners, &degradation, &mut rng)
+            .unwrap()
+            .image
+            .convert()
+    }
+
+    #[test]
+    fn test_decode_rotated_and_tilted_symbols() {
+        for (seed, rotation) in [(1, 30.0), (2, -75.0), (3, 180.0), (4, 125.0)] {
+            let degradation = Degradation {
+                blur: 0.7,
+                noise: 0.0,
+                skew: 0.12,
+                rotation,
+                illumination: 0.0,
+                jpeg_quality: None,
+            };
+            let img = capture("TILTED 42", degradation, seed);
+
+            assert!(read(&img).is_err());
+            assert_eq!(decode_image(&img).unwrap().text, "TILTED 42");
+        }
+    }
+
+    #[test]
+    fn test_decode_shadowed_symbol() {
+        let degradation = Degradation {
+            blur: 0.5,
+            noise: 4.0,
+            skew: 0.0,
+            rotation: 20.0,
+            illumination: 0.8,
+            jpeg_quality: None,
+        };
+        let img = capture("SHADOW", degradation, 6);
+
+        assert!(read_perspective(&img, &Binariser::GLOBAL).is_err());
+        assert_eq!(decode_image(&img).unwrap().text, "SHADOW");
+    }
+
+    #[test]
+    fn test_perspective_transform_fits_symbol() {
+        let degradation = Degradation {
+            blur: 0.0,
+            noise: 0.0,
+            skew: 0.0,
+            rotation: 90.0,
+            illumination: 0.0,
+            jpeg_quality: None,
+        };
+        let img = capture("QUARTER TURN", degradation, 5);
+        let (decoded, homography) = read_perspective(&img, &Binariser::GLOBAL).unwrap();
+
+        // A quarter turn clockwise puts the symbol's top left corner at the top right
+        let (x, y) = homography.map((0.0, 0.0));
+        assert_eq!(decoded.text, "QUARTER TURN");
+        assert!((x - 152.0).abs() < 1.0 && (y - 16.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_read_all_symbols_of_mixed_versions() {
+        let tilted = Degradation {
+            blur: 0.5,
+            noise: 0.0,
+            skew: 0.05,
+            rotation: 0.0,
+            illumination: 0.0,
+            jpeg_quality: None,
+        };
+
+        // An M2-L symbol, 13 modules a side, holding "M2" in alphanumeric mode
+        let data = [0b1010_0111, 0b1100_0000, 0, 0, 0];
+        let gf = GF256::new();
+        let ec = error_correction::calculate_codewords(
+            &data,
+            &error_correction::generator(5, &gf),
+            &gf,
+            5,
+        );
+        let m2 = CodeMatrix::with_codewords(13, &[8; 10], &[&data[..], &ec].concat())
+            .with_data_mask(&DataMask::from_pattern_ref(0).unwrap())
+            .with_format_info(format_info::bch_word(1, 0));
+        let mut small = CodeMatrix::new();
+        for i in 0..13 {
+            for j in 0..13 {
+                small.write(i, j, m2.read(i, j));
+            }
+        }
+
+        let mut canvas = RgbaImage::from_pixel(520, 460, colour::WHITE);
+        let placed = [
+            (
+                capture(
+                    "FIRST",
+                    Degradation {
+                        rotation: 40.0,
+                        ..tilted
+                    },
+                    1,
+                ),
+                (10, 20),
+            ),
+            (
+                capture(
+                    "SECOND",
+                    Degradation {
+                        rotation: 200.0,
+                        ..tilted
+                    },
+                    2,
+                ),
+                (280, 10),
+            ),
+            (
+                symbol_image::render(&ImageOptions::new(8), &small),
+                (300, 280),
+            ),
+        ];
+        for (img, (x, y)) in &placed {
+            image::imageops::overlay(&mut canvas, img, *x, *y);
+        }
+
+        let found = read_all(&canvas).unwrap();
+
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].text.as_deref(), Some("SECOND"));
+        assert_eq!(found[1].text.as_deref(), Some("FIRST"));
+        assert!((found[1].orientation - 40.0).abs() < 5.0);
+        assert!((found[0].orientation + 160.0).abs() < 5.0);
+        assert_eq!(found[2].version, Some("M2-L"));
+        assert_eq!(found[2].text.as_deref(), Some("M2"));
+        assert_eq!(found[2].error, None);
+
+        // The M2-L symbol's far corner is 13 modules from its top left one, not 17
+        let (x, y) = found[2].corners[2];
+        assert!((x - 300.0 - 16.0 - 104.0).abs() < 2.0 && (y - 280.0 - 16.0 - 104.0).abs() < 2.0);
+    }
+
+    #[test]
+    fn test_decode_mirror_images() {
+        let mirrored = symbol_image::render(&ImageOptions::new(6), &encode("ETCHED").transposed());
+        let decoded = decode_image(&mirrored).unwrap();
+
+        assert_eq!(decoded.text, "ETCHED");
+        assert!(decoded.mirrored);
+
+        // Seen from the back, the finder pattern is in the top right corner
+        let img = symbol_image::render(&ImageOptions::new(6), &encode("ETCHED"));
+        let decoded = decode_image(&image::imageops::flip_horizontal(&img)).unwrap();
+
+        assert_eq!(decoded.text, "ETCHED");
+        assert!(decoded.mirrored);
+        assert!(!decode_image(&img).unwrap().mirrored);
+    }
+
+    #[test]
+    fn test_runs() {
+        let line = [false, true, true, false, true].into_iter();
+
+        assert_eq!(runs(line), vec![(1, 2), (4, 1)]);
+    }
+}
@@ -0,0 +1,502 @@
+use crate::code_matrix::CodeMatrix;
+use crate::print_size::{self, PageSize};
+use crate::symbol_image::{self, ImageOptions};
+use crate::symbol_svg::number;
+use crate::{symbol_pdf, symbol_svg};
+use image::{imageops, ImageBuffer, RgbaImage};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Resolution pages are laid out at when none is given.
+pub const DEFAULT_DPI: u32 = 300;
+
+const POINTS_PER_INCH: f64 = 72.0;
+
+// Crop marks start this far from the label edge and stop short of the page edge, all in mm
+const CROP_MARK_OFFSET: f64 = 1.0;
+const CROP_MARK_LENGTH: f64 = 5.0;
+const CROP_MARK_WIDTH: f64 = 0.1;
+
+/// Label sheet geometry, all lengths in millimetres.
+#[derive(Debug, Clone)]
+pub struct SheetLayout {
+    pub page: PageSize,
+    pub margin: f64,
+    /// Horizontal gap between columns and vertical gap between rows
+    pub gutter: (f64, f64),
+    pub columns: u32,
+    pub rows: u32,
+    pub crop_marks: bool,
+}
+
+/// A rectangle in millimetres: x, y, width and height.
+type Rect = (f64, f64, f64, f64);
+
+impl SheetLayout {
+    /// Most labels of the given size that fit across a length, with a gutter between each.
+    pub fn fit(length: f64, margin: f64, gutter: f64, label: f64) -> u32 {
+        (((length - 2.0 * margin + gutter) / (label + gutter)).floor() as u32).max(1)
+    }
+
+    pub fn labels_per_page(&self) -> usize {
+        (self.columns * self.rows) as usize
+    }
+
+    pub fn cell_size(&self) -> (f64, f64) {
+        let (gutter_x, gutter_y) = self.gutter;
+        let width = self.page.width - 2.0 * self.margin - (self.columns - 1) as f64 * gutter_x;
+        let height = self.page.height - 2.0 * self.margin - (self.rows - 1) as f64 * gutter_y;
+
+        (width / self.columns as f64, height / self.rows as f64)
+    }
+
+    /// Top-left corner of the label at a position on the page, filled row by row.
+    pub fn cell_origin(&self, i: usize) -> (f64, f64) {
+        let (cell_width, cell_height) = self.cell_size();
+        let column = (i % self.columns as usize) as f64;
+        let row = (i / self.columns as usize) as f64;
+
+        (
+            self.margin + column * (cell_width + self.gutter.0),
+            self.margin + row * (cell_height + self.gutter.1),
+        )
+    }
+
+    /// Checks every label has room for a symbol of the given size.
+    pub fn check_fit(&self, symbol_width: f64, symbol_height: f64) -> Result<(), String> {
+        let (cell_width, cell_height) = self.cell_size();
+
+        if cell_width <= 0.0 || cell_height <= 0.0 {
+            return Err(String::from(
+                "The margins and gutters leave no room for labels",
+            ));
+        }
+
+        if symbol_width > cell_width + 1e-9 || symbol_height > cell_height + 1e-9 {
+            return Err(format!(
+                "Symbol is {:.1} x {:.1} mm but each label is only {:.1} x {:.1} mm",
+                symbol_width, symbol_height, cell_width, cell_height
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Marks in the page margin lining up with every label edge.
+    pub fn crop_mark_rects(&self) -> Vec<Rect> {
+        let length = CROP_MARK_LENGTH.min(self.margin - CROP_MARK_OFFSET);
+
+        if !self.crop_marks || length <= 0.0 {
+            return Vec::new();
+        }
+
+        let (cell_width, cell_height) = self.cell_size();
+        let half = CROP_MARK_WIDTH / 2.0;
+        let top = self.margin - CROP_MARK_OFFSET - length;
+        let bottom = self.page.height - self.margin + CROP_MARK_OFFSET;
+        let left = self.margin - CROP_MARK_OFFSET - length;
+        let right = self.page.width - self.margin + CROP_MARK_OFFSET;
+
+        let mut rects = Vec::new();
+
+        for column in 0..self.columns as usize {
+            let (x, _) = self.cell_origin(column);
+
+            for edge in [x, x + cell_width] {
+                rects.push((edge - half, top, CROP_MARK_WIDTH, length));
+                rects.push((edge - half, bottom, CROP_MARK_WIDTH, length));
+            }
+        }
+
+        for row in 0..self.rows as usize {
+            let (_, y) = self.cell_origin(row * self.columns as usize);
+
+            for edge in [y, y + cell_height] {
+                rects.push((left, edge - half, length, CROP_MARK_WIDTH));
+                rects.push((right, edge - half, length, CROP_MARK_WIDTH));
+            }
+        }
+
+        rects
+    }
+
+    /// Where each symbol on a page goes, in dots, centred in its label with its own text and
+    /// snapped to whole dots.
+    fn placements(
+        &self,
+        options: &ImageOptions,
+        page: &[(String, CodeMatrix)],
+        dpi: u32,
+    ) -> Vec<(f64, f64)> {
+        let (cell_width, cell_height) = self.cell_size();
+
+        page.iter()
+            .enumerate()
+            .map(|(i, (text, _))| {
+                let layout = options.clone().with_hri_text(text).layout();
+                let (x, y) = self.cell_origin(i);
+                let x =
+                    print_size::mm_to_dots(x + cell_width / 2.0, dpi) - layout.width as f64 / 2.0;
+                let y =
+                    print_size::mm_to_dots(y + cell_height / 2.0, dpi) - layout.height as f64 / 2.0;
+
+                (x.round(), y.round())
+            })
+            .collect()
+    }
+}
+
+/// Renders the symbols on one raster image in a grid, filled row by row. Each symbol is centred in
+/// a cell sized to fit the largest one, with `gap` pixels between cells.
+pub fn render(
+    options: &ImageOptions,
+    symbols: &[(String, CodeMatrix)],
+    columns: u32,
+    gap: u32,
+) -> RgbaImage {
+    let cells: Vec<RgbaImage> = symbols
+        .iter()
+        .map(|(text, matrix)| symbol_image::render(&options.clone().with_hri_text(text), matrix))
+        .collect();
+
+    let cell_width = cells.iter().map(|cell| cell.width()).max().unwrap_or(0);
+    let cell_height = cells.iter().map(|cell| cell.height()).max().unwrap_or(0);
+    let columns = columns.clamp(1, cells.len().max(1) as u32);
+    let rows = (cells.len() as u32).div_ceil(columns);
+
+    let width = columns * cell_width + columns.saturating_sub(1) * gap;
+    let height = rows * cell_height + rows.saturating_sub(1) * gap;
+    let mut sheet = ImageBuffer::from_pixel(width, height, options.background);
+
+    for (i, cell) in cells.iter().enumerate() {
+        let column = i as u32 % columns;
+        let row = i as u32 / columns;
+
+        let x = column * (cell_width + gap) + (cell_width - cell.width()) / 2;
+        let y = row * (cell_height + gap) + (cell_height - cell.height()) / 2;
+
+        imageops::replace(&mut sheet, cell, x as i64, y as i64);
+    }
+
+    sheet
+}
+
+/// Renders the labels as raster pages, filled with the background colour.
+pub fn raster_pages(
+    sheet: &SheetLayout,
+    options: &ImageOptions,
+    symbols: &[(String, CodeMatrix)],
+) -> Vec<RgbaImage> {
+    let dpi = options.dpi.unwrap_or(DEFAULT_DPI);
+    let width = print_size::mm_to_dots(sheet.page.width, dpi).round() as u32;
+    let height = print_size::mm_to_dots(sheet.page.height, dpi).round() as u32;
+
+    symbols
+        .chunks(sheet.labels_per_page())
+        .map(|page| {
+            let mut img = ImageBuffer::from_pixel(width, height, options.background);
+
+            for (x, y, w, h) in sheet.crop_mark_rects() {
+                let x0 = print_size::mm_to_dots(x, dpi).round() as u32;
+                let y0 = print_size::mm_to_dots(y, dpi).round() as u32;
+
+                // Never thinner than a single dot
+                let x1 = (print_size::mm_to_dots(x + w, dpi).round() as u32).max(x0 + 1);
+                let y1 = (print_size::mm_to_dots(y + h, dpi).round() as u32).max(y0 + 1);
+
+                for py in y0..y1.min(height) {
+                    for px in x0..x1.min(width) {
+                        img.put_pixel(px, py, options.foreground);
+                    }
+                }
+            }
+
+            let placements = sheet.placements(options, page, dpi);
+
+            for ((text, matrix), (x, y)) in page.iter().zip(placements) {
+                let symbol = symbol_image::render(&options.clone().with_hri_text(text), matrix);
+                imageops::replace(&mut img, &symbol, x as i64, y as i64);
+            }
+
+            img
+        })
+        .collect()
+}
+
+/// Renders the labels as one SVG document per page, drawn in dots and sized in millimetres.
+pub fn svg_pages(
+    sheet: &SheetLayout,
+    options: &ImageOptions,
+    symbols: &[(String, CodeMatrix)],
+) -> Vec<String> {
+    let dpi = options.dpi.unwrap_or(DEFAULT_DPI);
+    let dots = |mm: f64| number(print_size::mm_to_dots(mm, dpi));
+
+    symbols
+        .chunks(sheet.labels_per_page())
+        .map(|page| {
+            let mut svg = String::new();
+
+            writeln!(
+                svg,
+                r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{}mm" height="{}mm" viewBox="0 0 {} {}" shape-rendering="crispEdges">"#,
+                number(sheet.page.width),
+                number(sheet.page.height),
+                dots(sheet.page.width),
+                dots(sheet.page.height)
+            )
+            .unwrap();
+
+            for (x, y, w, h) in sheet.crop_mark_rects() {
+                writeln!(
+                    svg,
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" {}/>"#,
+                    dots(x),
+                    dots(y),
+                    dots(w),
+                    dots(h),
+                    symbol_svg::fill(options.foreground)
+                )
+                .unwrap();
+            }
+
+            let placements = sheet.placements(options, page, dpi);
+
+            for ((text, matrix), (x, y)) in page.iter().zip(placements) {
+                writeln!(svg, r#"<g transform="translate({} {})">"#, x, y).unwrap();
+                svg.push_str(&symbol_svg::elements(
+                    &options.clone().with_hri_text(text),
+                    matrix,
+                ));
+                svg.push_str("</g>\n");
+            }
+
+            svg.push_str("</svg>\n");
+
+            svg
+        })
+        .collect()
+}
+
+/// Renders the labels as a PDF document with as many pages as needed.
+pub fn pdf(
+    sheet: &SheetLayout,
+    options: &ImageOptions,
+    symbols: &[(String, CodeMatrix)],
+) -> Vec<u8> {
+    let dpi = options.dpi.unwrap_or(DEFAULT_DPI);
+    let scale = POINTS_PER_INCH / dpi as f64;
+    let page_width = print_size::mm_to_dots(sheet.page.width, dpi) * scale;
+    let page_height = print_size::mm_to_dots(sheet.page.height, dpi) * scale;
+
+    let pages: Vec<String> = symbols
+        .chunks(sheet.labels_per_page())
+        .map(|page| {
+            let mut content = String::new();
+
+            // Same top-down dot units as a single symbol
+            writeln!(
+                content,
+                "{} 0 0 {} 0 {} cm",
+                number(scale),
+                number(-scale),
+                number(page_height)
+            )
+            .unwrap();
+
+            let crop_marks = sheet.crop_mark_rects();
+
+            if !crop_marks.is_empty() {
+                writeln!(content, "q {}", symbol_pdf::fill(options.foreground)).unwrap();
+
+                for (x, y, w, h) in crop_marks {
+                    let dots = |mm: f64| number(print_size::mm_to_dots(mm, dpi));
+                    writeln!(
+                        content,
+                        "{} {} {} {} re f",
+                        dots(x),
+                        dots(y),
+                        dots(w),
+                        dots(h)
+                    )
+                    .unwrap();
+                }
+
+                content.push_str("Q\n");
+            }
+
+            let placements = sheet.placements(options, page, dpi);
+
+            for ((text, matrix), (x, y)) in page.iter().zip(placements) {
+                writeln!(content, "q 1 0 0 1 {} {} cm", x, y).unwrap();
+                content.push_str(&symbol_pdf::symbol_content(
+                    &options.clone().with_hri_text(text),
+                    matrix,
+                ));
+                content.push_str("Q\n");
+            }
+
+            content
+        })
+        .collect();
+
+    symbol_pdf::document(
+        page_width,
+        page_height,
+        &pages,
+        &[options.foreground, options.background],
+    )
+}
+
+/// File name for one page of a multi-page sheet, numbered from 1 before the extension.
+pub fn page_file_name(file_name: &str, page: usize) -> String {
+    let path = Path::new(file_name);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("");
+
+    let name = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(extension) => format!("{}-{}.{}", stem, page, extension),
+        None => format!("{}-{}", stem, page),
+    };
+
+    path.with_file_name(name).to_string_lossy().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbol_image::Hri;
+    use crate::{data_codewords, symbol_matrix};
+
+    fn symbol(text: &str) -> (String, CodeMatrix) {
+        let matrix = symbol_matrix::generate(&data_codewords::generate(text), None);
+        (text.to_string(), matrix)
+    }
+
+    fn test_sheet() -> SheetLayout {
+        SheetLayout {
+            page: PageSize {
+                width: 96.0,
+                height: 60.0,
+            },
+            margin: 10.0,
+            gutter: (2.0, 0.0),
+            columns: 3,
+            rows: 2,
+            crop_marks: true,
+        }
+    }
+
+    #[test]
+    fn test_sheet_geometry() {
+        let sheet = test_sheet();
+
+        assert_eq!(sheet.labels_per_page(), 6);
+        assert_eq!(sheet.cell_size(), (24.0, 20.0));
+        assert_eq!(sheet.cell_origin(4), (36.0, 30.0));
+        assert!(sheet.check_fit(24.0, 20.0).is_ok());
+        assert!(sheet.check_fit(25.0, 10.0).is_err());
+        assert_eq!(SheetLayout::fit(96.0, 10.0, 2.0, 24.0), 3);
+        assert_eq!(SheetLayout::fit(100.0, 10.0, 2.0, 200.0), 1);
+    }
+
+    #[test]
+    fn test_crop_marks_sit_in_the_margin() {
+        let sheet = test_sheet();
+        let marks = sheet.crop_mark_rects();
+
+        // Two edges per column and per row, marked on both sides
+        assert_eq!(marks.len(), 3 * 2 * 2 + 2 * 2 * 2);
+        assert!(marks.contains(&(9.95, 4.0, 0.1, 5.0)));
+        assert!(marks.contains(&(87.0, 49.95, 5.0, 0.1)));
+
+        let no_marks = SheetLayout {
+            crop_marks: false,
+            ..test_sheet()
+        };
+        assert!(no_marks.crop_mark_rects().is_empty());
+    }
+
+    #[test]
+    fn test_pages_paginate() {
+        let options = ImageOptions {
+            dpi: Some(254),
+            ..ImageOptions::new(4)
+        };
+        let symbols: Vec<_> = (1..=8).map(|i| symbol(&format!("A{}", i))).collect();
+        let sheet = test_sheet();
+
+        let pages = raster_pages(&sheet, &options, &symbols);
+        assert_eq!(pages.len(), 2);
+
+        // 96 x 60 mm at 10 dots per mm
+        assert_eq!(pages[0].dimensions(), (960, 600));
+
+        // The first 84 dot symbol is centred in the 240 x 200 dot label at (100, 100),
+        // its finder pattern starting after the 8 dot quiet zone
+        assert_eq!(*pages[0].get_pixel(178 + 8, 158 + 8), options.foreground);
+        assert_eq!(*pages[0].get_pixel(178 + 7, 158 + 8), options.background);
+
+        let svg = svg_pages(&sheet, &options, &symbols);
+        assert_eq!(svg.len(), 2);
+        assert!(svg[1].contains(r#"width="96mm" height="60mm" viewBox="0 0 960 600""#));
+        assert_eq!(svg[1].matches("<g transform").count(), 2);
+
+        let pdf = String::from_utf8(pdf(&sheet, &options, &symbols)).unwrap();
+        assert!(pdf.contains("/Count 2"));
+        assert!(pdf.contains("q 1 0 0 1 178 158 cm"));
+    }
+
+    #[test]
+    fn test_pages_centre_labels_with_text() {
+        let options = ImageOptions {
+            dpi: Some(254),
+            hri: Some(Hri {
+                text: String::new(),
+                font_size: 14,
+                spacing: 4,
+            }),
+            ..ImageOptions::new(4)
+        };
+        let symbols = [symbol("ABCDEFGHIJKLMNOP")];
+        let sheet = test_sheet();
+
+        // The 198 x 106 dot label, text wider than the symbol, is centred on (220, 200)
+        assert_eq!(sheet.placements(&options, &symbols, 254), [(121.0, 147.0)]);
+
+        // So the symbol itself stays centred across the label
+        let pages = raster_pages(&sheet, &options, &symbols);
+        assert_eq!(*pages[0].get_pixel(178 + 8, 147 + 8), options.foreground);
+        assert_eq!(*pages[0].get_pixel(178 + 7, 147 + 8), options.background);
+    }
+
+    #[test]
+    fn test_page_file_name() {
+        assert_eq!(page_file_name("labels.png", 2), "labels-2.png");
+        assert_eq!(page_file_name("out/labels.svg", 1), "out/labels-1.svg");
+        assert_eq!(page_file_name("labels", 3), "labels-3");
+    }
+
+    #[test]
+    fn test_render_grid() {
+        let options = ImageOptions::new(1);
+        let symbols = [symbol("A1"), symbol("A2"), symbol("A3")];
+
+        let sheet = render(&options, &symbols, 2, 3);
+
+        // Two 21 pixel cells across with a 3 pixel gap, two rows down
+        assert_eq!(sheet.dimensions(), (45, 45));
+
+        // Top-left finder corner of the symbol in the second column
+        assert_eq!(*sheet.get_pixel(24 + 2, 2), options.foreground);
+        assert_eq!(*sheet.get_pixel(22, 2), options.background);
+
+        // The fourth cell is empty
+        assert!(sheet
+            .enumerate_pixels()
+            .filter(|(x, y, _)| *x >= 24 && *y >= 24)
+            .all(|(_, _, pixel)| *pixel == options.background));
+    }
+}
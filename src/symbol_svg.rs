@@ -0,0 +1,200 @@
+use crate::code_matrix::CodeMatrix;
+use crate::print_gain;
+use crate::symbol_image::ImageOptions;
+use crate::symbol_pdf::CAP_HEIGHT;
+use image::Rgba;
+use std::fmt::Write;
+
+const MM_PER_INCH: f64 = 25.4;
+
+pub fn render(options: &ImageOptions, matrix: &CodeMatrix) -> String {
+    let (width, height) = (options.layout().width, options.layout().height);
+
+    let mut svg = String::new();
+
+    // With a known printer resolution the drawing is given its physical size
+    let physical = |pixels: u32| match options.dpi {
+        Some(dpi) => format!("{}mm", number(pixels as f64 / dpi as f64 * MM_PER_INCH)),
+        None => pixels.to_string(),
+    };
+
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" version="1.1" width="{}" height="{}" viewBox="0 0 {} {}" shape-rendering="crispEdges">"#,
+        physical(width),
+        physical(height),
+        width,
+        height
+    )
+    .unwrap();
+
+    svg.push_str(&elements(options, matrix));
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+/// The background, modules and text of the symbol, in pixel units from the top-left of its layout.
+pub fn elements(options: &ImageOptions, matrix: &CodeMatrix) -> String {
+    let module_size = options.module_size as f64;
+    let layout = options.layout();
+    let offset_x = (layout.symbol_x + options.quiet_zone * options.module_size) as f64;
+    let offset_y = (options.quiet_zone * options.module_size) as f64;
+    let (width, height) = (layout.width, layout.height);
+
+    let mut elements = String::new();
+
+    if options.background[3] > 0 {
+        writeln!(
+            elements,
+            r#"<rect x="0" y="0" width="{}" height="{}" {}/>"#,
+            width,
+            height,
+            fill(options.background)
+        )
+        .unwrap();
+    }
+
+    let mut path = String::new();
+
+    for (y, x, outline) in print_gain::dark_module_outlines(matrix, options.print_gain) {
+        let (origin_x, origin_y) = (
+            offset_x + x as f64 * module_size,
+            offset_y + y as f64 * module_size,
+        );
+
+        for (i, (px, py)) in outline.polygon(module_size).into_iter().enumerate() {
+            let command = if i == 0 { 'M' } else { 'L' };
+            write!(
+                path,
+                "{}{} {}",
+                command,
+                number(origin_x + px),
+                number(origin_y + py)
+            )
+            .unwrap();
+        }
+
+        path.push('Z');
+    }
+
+    writeln!(
+        elements,
+        r#"<path d="{}" {}/>"#,
+        path,
+        fill(options.foreground)
+    )
+    .unwrap();
+
+    if let Some(hri) = &options.hri {
+        writeln!(
+            elements,
+            r#"<text x="{}" y="{}" font-family="Courier, monospace" font-size="{}" text-anchor="middle" {}>{}</text>"#,
+            number(width as f64 / 2.0),
+            layout.text_y + layout.text_height,
+            number(layout.text_height as f64 / CAP_HEIGHT),
+            fill(options.foreground),
+            escape(&hri.text)
+        )
+        .unwrap();
+    }
+
+    elements
+}
+
+pub fn fill(colour: Rgba<u8>) -> String {
+    let Rgba([r, g, b, a]) = colour;
+    let mut attributes = format!(r##"fill="#{:02x}{:02x}{:02x}""##, r, g, b);
+
+    if a < 255 {
+        write!(
+            attributes,
+            r#" fill-opacity="{}""#,
+            number(a as f64 / 255.0)
+        )
+        .unwrap();
+    }
+
+    attributes
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Formats a coordinate with at most three decimals and no trailing zeros.
+pub fn number(value: f64) -> String {
+    let formatted = format!("{:.3}", value);
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+
+    if trimmed == "-0" {
+        String::from("0")
+    } else {
+        trimmed.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour;
+    use crate::symbol_image::Hri;
+
+    #[test]
+    fn test_number() {
+        assert_eq!(number(10.0), "10");
+        assert_eq!(number(1.5), "1.5");
+        assert_eq!(number(0.3333333), "0.333");
+        assert_eq!(number(-0.0001), "0");
+    }
+
+    #[test]
+    fn test_render_single_module() {
+        let mut matrix = CodeMatrix::new();
+        matrix.write(0, 0, 1);
+
+        let svg = render(&ImageOptions::new(10), &matrix);
+
+        assert!(svg.contains(r#"viewBox="0 0 210 210""#));
+        assert!(svg.contains(r##"<rect x="0" y="0" width="210" height="210" fill="#ffffff"/>"##));
+        assert!(svg.contains(r##"<path d="M20 20L30 20L30 30L20 30Z" fill="#000000"/>"##));
+    }
+
+    #[test]
+    fn test_render_physical_size_and_transparency() {
+        let options = ImageOptions {
+            dpi: Some(254),
+            background: colour::TRANSPARENT,
+            print_gain: 2.0,
+            ..ImageOptions::new(10)
+        };
+
+        let mut matrix = CodeMatrix::new();
+        matrix.write(0, 0, 1);
+
+        let svg = render(&options, &matrix);
+
+        assert!(svg.contains(r#"width="21mm" height="21mm""#));
+        assert!(!svg.contains("<rect"));
+        assert!(svg.contains("M21 21L29 21L29 29L21 29Z"));
+    }
+
+    #[test]
+    fn test_render_hri_text_element() {
+        let options = ImageOptions {
+            hri: Some(Hri {
+                text: String::from("A&B"),
+                font_size: 14,
+                spacing: 10,
+            }),
+            ..ImageOptions::new(10)
+        };
+
+        let svg = render(&options, &CodeMatrix::new());
+
+        assert!(svg.contains(r#"viewBox="0 0 210 244""#));
+        assert!(svg.contains(r##"<text x="105" y="234" font-family="Courier, monospace" font-size="20" text-anchor="middle" fill="#000000">A&amp;B</text>"##));
+    }
+}
@@ -0,0 +1,69 @@
+/// Expands `{name}` placeholders in a template. Number placeholders may be zero padded as
+/// `{name:06}`, text placeholders are inserted as they are.
+pub fn expand(
+    template: &str,
+    numbers: &[(&str, u64)],
+    texts: &[(&str, &str)],
+) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("Unclosed placeholder in template {}", template))?;
+        let placeholder = &rest[start + 1..start + end];
+        let (name, width) = match placeholder.split_once(':') {
+            Some((name, width)) => (name, Some(width)),
+            None => (placeholder, None),
+        };
+
+        let number = numbers.iter().find(|(n, _)| *n == name).map(|(_, v)| *v);
+        let text = texts.iter().find(|(n, _)| *n == name).map(|(_, v)| *v);
+
+        match (number, text, width) {
+            (Some(number), _, Some(width)) => {
+                let width: usize = width
+                    .parse()
+                    .map_err(|_| format!("Invalid padding in placeholder {{{}}}", placeholder))?;
+                expanded.push_str(&format!("{:0width$}", number, width = width));
+            }
+            (Some(number), _, None) => expanded.push_str(&number.to_string()),
+            (None, Some(text), None) => expanded.push_str(text),
+            _ => return Err(format!("Unknown placeholder {{{}}}", placeholder)),
+        }
+
+        rest = &rest[start + end + 1..];
+    }
+
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let numbers = [("seq", 42)];
+        let texts = [("data", "ABC")];
+
+        assert_eq!(
+            expand("LOT-{seq:06}", &numbers, &texts).unwrap(),
+            "LOT-000042"
+        );
+        assert_eq!(
+            expand("{data}-{seq}.png", &numbers, &texts).unwrap(),
+            "ABC-42.png"
+        );
+        assert_eq!(expand("PLAIN", &numbers, &texts).unwrap(), "PLAIN");
+        assert!(expand("{data:3}", &numbers, &texts).is_err());
+        assert!(expand("{seq:x}", &numbers, &texts).is_err());
+        assert!(expand("{row}", &numbers, &texts).is_err());
+        assert!(expand("{seq", &numbers, &texts).is_err());
+    }
+}
@@ -0,0 +1,80 @@
+//! JavaScript API of the wasm32 build, generating symbols in memory without touching the file
+//! system. The same functions are plain Rust functions on other targets.
+
+use crate::symbol_image::ImageOptions;
+use crate::{symbol_matrix, symbol_svg};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+const MODULE_COUNT: usize = 17;
+
+/// The modules of the symbol for an input string, row by row from the top left, 1 for dark, in a
+/// single array of 17x17 values. Without a mask pattern (0-3) the best scoring one is used.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = encodeMatrix))]
+pub fn encode_matrix(input: &str, mask: Option<u8>) -> Result<Vec<u8>, String> {
+    let matrix = symbol_matrix::encode(input, mask)?;
+
+    Ok((0..MODULE_COUNT)
+        .flat_map(|y| (0..MODULE_COUNT).map(move |x| (y, x)))
+        .map(|(y, x)| matrix.read(y, x))
+        .collect())
+}
+
+/// The symbol for an input string as an SVG document, with the module size in pixels and the quiet
+/// zone in modules.
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen(js_name = encodeSvg))]
+pub fn encode_svg(
+    input: &str,
+    module_size: u32,
+    quiet_zone: u32,
+    mask: Option<u8>,
+) -> Result<String, String> {
+    if module_size == 0 {
+        return Err(String::from("Module size must be at least 1"));
+    }
+
+    let matrix = symbol_matrix::encode(input, mask)?;
+    let options = ImageOptions {
+        quiet_zone,
+        ..ImageOptions::new(module_size)
+    };
+
+    Ok(symbol_svg::render(&options, &matrix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_mask::DataMask;
+    use crate::{data_codewords, symbol_matrix};
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    #[test]
+    fn test_encode_matrix() {
+        let modules = encode_matrix("HELLO", Some(2)).unwrap();
+        let matrix = symbol_matrix::generate(
+            &data_codewords::generate("HELLO"),
+            Some(DataMask::pattern_10()),
+        );
+
+        assert_eq!(modules.len(), 17 * 17);
+        assert_eq!(modules[3 * 17 + 16], matrix.read(3, 16));
+        assert_eq!(&modules[..8], &[1, 1, 1, 1, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn test_encode_svg() {
+        let svg = encode_svg("HELLO", 4, 2, None).unwrap();
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("width=\"84\""));
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(encode_matrix("hello", None).is_err());
+        assert!(encode_matrix("HELLO", Some(4)).is_err());
+        assert!(encode_svg("HELLO", 0, 2, None).is_err());
+    }
+}